@@ -1,4 +1,4 @@
-use alloc::{boxed::Box, sync::Arc};
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
 use core::{
     any::Any,
     fmt,
@@ -8,8 +8,9 @@ use core::{
 
 use crate::{
     hash::Hash,
-    pair::Pair,
-    params::{SearchParams, SEARCH_PARAMS},
+    pair::{Pair, BLACK, WHITE},
+    params::{EvalFeatures, EvalParams, SearchParams, SEARCH_PARAMS},
+    state::Symmetry,
 };
 
 pub struct Options {
@@ -18,6 +19,8 @@ pub struct Options {
     pub half_komi: i32,
 
     pub params: SearchParams,
+    /// `None` selects the defaults for the board size.
+    pub eval: Option<EvalParams>,
 }
 
 impl Options {
@@ -38,6 +41,7 @@ impl Options {
             half_komi: 0,
 
             params: SEARCH_PARAMS,
+            eval: None,
         })
     }
 }
@@ -135,15 +139,109 @@ impl Eval {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Proof {
+    Win,
+    Loss,
+    Draw,
+    Unknown,
+}
+
+impl fmt::Display for Proof {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Win => "win",
+            Self::Loss => "loss",
+            Self::Draw => "draw",
+            Self::Unknown => "unknown",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameResult {
+    Road(bool),
+    Flat(bool),
+    Draw,
+}
+
+impl GameResult {
+    /// Returns the score of white: 1 for a win, 0.5 for a draw and 0 for a loss.
+    pub fn white_score(self) -> f64 {
+        match self {
+            Self::Road(color) | Self::Flat(color) if color == WHITE => 1.,
+            Self::Road(_) | Self::Flat(_) => 0.,
+            Self::Draw => 0.5,
+        }
+    }
+}
+
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Road(WHITE) => "R-0",
+            Self::Road(BLACK) => "0-R",
+            Self::Flat(WHITE) => "F-0",
+            Self::Flat(BLACK) => "0-F",
+            Self::Draw => "1/2-1/2",
+        })
+    }
+}
+
 #[derive(Debug)]
 pub struct ParseMoveError;
 
-#[derive(Debug)]
-pub struct PlayMoveError;
+/// Reason why [`Game::play`] rejected a move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayMoveError {
+    Pass,
+    /// The first move of each side places a flat, and nothing else.
+    Opening,
+    Occupied,
+    NoStones,
+    NoCaps,
+    /// The stack to spread is empty or controlled by the opponent.
+    NotControlled,
+    /// The spread takes more pieces than the stack holds.
+    CarryLimit,
+    OffBoard,
+    /// A wall or a cap is in the way, and cannot be flattened by this spread.
+    Blocked,
+}
+
+impl fmt::Display for PlayMoveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Pass => "passing is not allowed",
+            Self::Opening => "the first moves place a flat of the opponent",
+            Self::Occupied => "the square is occupied",
+            Self::NoStones => "there are no stones left",
+            Self::NoCaps => "there are no caps left",
+            Self::NotControlled => "the stack is not controlled by the side to move",
+            Self::CarryLimit => "the stack does not hold that many pieces",
+            Self::OffBoard => "the spread leaves the board",
+            Self::Blocked => "a wall or a cap is in the way",
+        })
+    }
+}
 
 #[derive(Debug)]
 pub struct SetPositionError;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TablebaseError {
+    /// More positions are reachable than the solver can hold.
+    TooManyPositions,
+    /// Two solved positions have the same hash, which probing could not tell apart.
+    HashCollision,
+    Malformed,
+    /// The table was solved for different reserves or komi.
+    Mismatch,
+}
+
+#[derive(Debug)]
+pub struct NetworkError;
+
 pub struct AbortFlag(Arc<AtomicBool>);
 
 impl AbortFlag {
@@ -161,19 +259,51 @@ impl AbortFlag {
 pub trait Game: Send {
     fn search(&mut self, depth: u32) -> Option<(Eval, Box<dyn Move>)>;
     fn perft(&mut self, depth: u32, mode: PerftMode) -> u64;
+    fn eval_features(&self) -> EvalFeatures;
+    fn result(&mut self) -> Option<GameResult>;
+    fn prove(&mut self, max_nodes: u64) -> Proof;
+    /// Writes a tablebase of every position reachable from the current one.
+    ///
+    /// All positions are held in memory, so this only handles reduced reserves or positions late
+    /// in the game. The full 3x3 game is out of reach.
+    fn solve(&mut self, max_positions: usize) -> Result<Vec<u8>, TablebaseError>;
+    fn set_tablebase(&mut self, data: &[u8]) -> Result<(), TablebaseError>;
+    fn set_network(&mut self, data: &[u8]) -> Result<(), NetworkError>;
     fn parser(&mut self) -> fn(&str) -> Result<Box<dyn Move>, ParseMoveError>;
+    /// Parser for moves in PlayTak server notation, such as `P C3 W` or `M A1 A4 2 1`.
+    fn playtak_parser(&mut self) -> fn(&str) -> Result<Box<dyn Move>, ParseMoveError>;
+    fn playtak_move(&self, mv: Box<dyn Move>) -> Box<dyn fmt::Display>;
     fn play(&mut self, mv: Box<dyn Move>) -> Result<(), PlayMoveError>;
+    /// Whether [`Game::play`] would accept the move.
+    fn is_legal(&mut self, mv: Box<dyn Move>) -> bool;
+    /// Whether the move covers a stack of the opponent.
+    fn is_tactical(&self, mv: Box<dyn Move>) -> bool;
     fn set_position(&mut self, tps: &str) -> Result<(), SetPositionError>;
+    fn encode_position(&self) -> Vec<u8>;
+    fn decode_position(&mut self, data: &[u8]) -> Result<(), SetPositionError>;
+    fn transform(&mut self, sym: Symmetry);
+    /// Hash which is the same for all symmetric images of the position.
+    fn canonical_hash(&self) -> Hash;
+    fn transform_move(&self, mv: Box<dyn Move>, sym: Symmetry) -> Box<dyn Move>;
     fn pv(&mut self) -> Box<dyn fmt::Display + '_>;
+    fn eval_trace(&self) -> Box<dyn fmt::Display + '_>;
+    fn tps(&self) -> Box<dyn fmt::Display + '_>;
+    /// Board with the full contents of each stack, the walls and caps, the reserves, the hash and
+    /// the static eval.
+    fn board(&self) -> Box<dyn fmt::Display + '_>;
     fn abort_flag(&self) -> AbortFlag;
     fn clear_abort_flag(&self) -> bool;
     fn swap_abort_flags(&mut self);
     fn nodes(&self) -> u64;
     fn clear_nodes(&mut self);
+    /// Sets the abort flag once the search has counted `limit` nodes since the last
+    /// [`Game::clear_nodes`]. `u64::MAX` disables the limit.
+    fn set_node_limit(&mut self, limit: u64);
     fn hash(&mut self) -> Hash;
     fn stones_left(&self) -> Pair<u32>;
     fn caps_left(&self) -> Pair<u32>;
     fn active_color(&self) -> bool;
+    fn ply(&self) -> u32;
     fn is_opening(&self) -> bool;
 }
 
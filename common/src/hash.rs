@@ -12,6 +12,11 @@ impl Hash {
     pub const ZERO: Self = Self(0);
     pub const SIDE_TO_MOVE: Self = Self(0xf812ec2e34a9c388); // 1815ad0c9e50c110
 
+    #[inline]
+    pub fn raw(self) -> u64 {
+        self.0
+    }
+
     /// # Panics
     ///
     /// The method may panic if the `len` provided is zero or is not a power of two.
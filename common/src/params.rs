@@ -7,7 +7,10 @@ pub struct SearchParams {
     pub nmp_factor: u32,
     pub nmp_fudge: i32,
     pub nmp_eval_margin: i32,
+    pub endgame_reserves: u32,
+    pub endgame_empty: u32,
     pub tt_size: usize,
+    pub eval_cache_size: usize,
 }
 
 pub static SEARCH_PARAMS: SearchParams = SearchParams {
@@ -18,5 +21,109 @@ pub static SEARCH_PARAMS: SearchParams = SearchParams {
     nmp_factor: 2,
     nmp_fudge: 5,
     nmp_eval_margin: 5,
+    endgame_reserves: 3,
+    endgame_empty: 2,
     tt_size: 1 << 24,
+    eval_cache_size: 1 << 12,
 };
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EvalWeights {
+    pub flat_count: i32,
+    pub stones_left: i32,
+    pub caps_left: i32,
+    pub total_dist: i32,
+    pub smallest_dist: i32,
+    pub side_to_move: i32,
+    pub reserves: i32,
+    pub captives: i32,
+    pub cap_stack: i32,
+}
+
+/// Evaluation weights, interpolated between `opening` and `endgame` by the game phase.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EvalParams {
+    pub max_dist_offset: i32,
+
+    pub opening: EvalWeights,
+    pub endgame: EvalWeights,
+}
+
+/// Defaults shared by all board sizes, which may override them.
+///
+/// The endgame weights equal the opening ones, so the taper has no effect until a tuning run
+/// finds a better endgame set.
+pub const EVAL_PARAMS: EvalParams = EvalParams {
+    max_dist_offset: -1,
+
+    opening: EvalWeights {
+        flat_count: 10,
+        stones_left: -7,
+        caps_left: -15,
+        total_dist: -1,
+        smallest_dist: -2,
+        side_to_move: 21,
+        reserves: 3,
+        captives: 1,
+        cap_stack: 2,
+    },
+    endgame: EvalWeights {
+        flat_count: 10,
+        stones_left: -7,
+        caps_left: -15,
+        total_dist: -1,
+        smallest_dist: -2,
+        side_to_move: 21,
+        reserves: 3,
+        captives: 1,
+        cap_stack: 2,
+    },
+};
+
+/// Phase at the start of the game, decreasing to 0 as the game nears its end.
+pub const PHASE_MAX: i32 = 256;
+
+/// Evaluation terms from the point of view of the side to move.
+///
+/// The evaluation is the sum of each term multiplied by the [`EvalWeights`] weight of the same
+/// name, tapered between the opening and endgame weights by `phase`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EvalFeatures {
+    pub flat_count: i32,
+    pub stones_left: i32,
+    pub caps_left: i32,
+    pub total_dist: i32,
+    pub smallest_dist: i32,
+    pub side_to_move: i32,
+    /// Own pieces under own top pieces, within the carry limit.
+    pub reserves: i32,
+    /// Opponent pieces under own top pieces, within the carry limit.
+    pub captives: i32,
+    /// Pieces under own capstones, within the carry limit.
+    pub cap_stack: i32,
+    /// Game phase, from [`PHASE_MAX`] in the opening to 0 in the endgame.
+    pub phase: i32,
+}
+
+impl EvalFeatures {
+    #[inline]
+    pub fn weigh(&self, params: &EvalParams) -> i32 {
+        let opening = self.weigh_untapered(&params.opening);
+        let endgame = self.weigh_untapered(&params.endgame);
+
+        (opening * self.phase + endgame * (PHASE_MAX - self.phase)) / PHASE_MAX
+    }
+
+    #[inline]
+    pub fn weigh_untapered(&self, params: &EvalWeights) -> i32 {
+        self.flat_count * params.flat_count
+            + self.stones_left * params.stones_left
+            + self.caps_left * params.caps_left
+            + self.total_dist * params.total_dist
+            + self.smallest_dist * params.smallest_dist
+            + self.side_to_move * params.side_to_move
+            + self.reserves * params.reserves
+            + self.captives * params.captives
+            + self.cap_stack * params.cap_stack
+    }
+}
@@ -78,3 +78,65 @@ impl Neg for Direction {
         unsafe { transmute(self as u32 ^ 2) }
     }
 }
+
+/// One of the eight symmetries of the board: an optional transposition followed by optional
+/// mirroring of the columns and rows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Symmetry(u8);
+
+impl Symmetry {
+    const MIRROR_COLS: u8 = 1;
+    const MIRROR_ROWS: u8 = 2;
+    const TRANSPOSE: u8 = 4;
+
+    pub const IDENTITY: Self = Self(0);
+    pub const ALL: [Self; 8] = [
+        Self(0),
+        Self(1),
+        Self(2),
+        Self(3),
+        Self(4),
+        Self(5),
+        Self(6),
+        Self(7),
+    ];
+
+    #[inline]
+    pub fn mirrors_cols(self) -> bool {
+        self.0 & Self::MIRROR_COLS != 0
+    }
+
+    #[inline]
+    pub fn mirrors_rows(self) -> bool {
+        self.0 & Self::MIRROR_ROWS != 0
+    }
+
+    #[inline]
+    pub fn transposes(self) -> bool {
+        self.0 & Self::TRANSPOSE != 0
+    }
+
+    /// Returns the symmetry which undoes this one.
+    #[inline]
+    pub fn inverse(self) -> Self {
+        if self.transposes() {
+            Self(self.0 & Self::TRANSPOSE | (self.0 & 1) << 1 | (self.0 & 2) >> 1)
+        } else {
+            self
+        }
+    }
+
+    #[inline]
+    pub fn direction(self, dir: Direction) -> Direction {
+        let mut dir = dir as u32;
+        if self.transposes() {
+            dir ^= 1;
+        }
+        // Horizontal directions are even, vertical ones odd
+        if dir & 1 == 0 && self.mirrors_cols() || dir & 1 != 0 && self.mirrors_rows() {
+            dir ^= 2;
+        }
+
+        unsafe { transmute(dir) }
+    }
+}
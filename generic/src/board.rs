@@ -0,0 +1,101 @@
+use crate::*;
+
+pub struct BoardWriter<'a>(&'a State);
+
+impl<'a> BoardWriter<'a> {
+    pub fn new(state: &'a State) -> Self {
+        Self(state)
+    }
+}
+
+impl<'a> fmt::Display for BoardWriter<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = self.0;
+        let road = s.road.white | s.road.black;
+        let block = s.block.white | s.block.black;
+
+        // Stack contents from bottom to top, as in TPS, with the top marked as a wall or a cap
+        let cells = (0..ARR_LEN).map(|i| {
+            let sq = sq(i);
+            let stack = s.stacks[sq];
+            let mut cell = (0..stack.height())
+                .rev()
+                .map(|depth| match stack.raw() >> depth & 1 != 0 {
+                    WHITE => '1',
+                    BLACK => '2',
+                })
+                .collect::<String>();
+
+            if block & sq.bit() != 0 {
+                cell += if road & sq.bit() != 0 { "C" } else { "S" };
+            }
+            cell
+        });
+        let cells = cells.collect::<Vec<_>>();
+
+        let width = cells.iter().map(String::len).max().unwrap_or(0).max(1);
+        let map_width = SIZE.max("walls".len());
+        let border = format!("   +{}", format!("{}+", "-".repeat(width + 2)).repeat(SIZE));
+
+        // Walls and caps of each color, read from the bitboards
+        let walls = Pair::new(s.block.white & !s.road.white, s.block.black & !s.road.black);
+        let caps = Pair::new(s.block.white & s.road.white, s.block.black & s.road.black);
+        let map = |f: &mut fmt::Formatter, pieces: Pair<Bitboard>, y| {
+            (0..SIZE).try_for_each(|x| {
+                let bit = sq(x + y * ROW_LEN).bit();
+                f.write_str(if pieces.white & bit != 0 {
+                    "1"
+                } else if pieces.black & bit != 0 {
+                    "2"
+                } else {
+                    "."
+                })
+            })
+        };
+
+        writeln!(f, "{border}  {:<map_width$}  caps", "walls")?;
+        for y in (0..SIZE).rev() {
+            write!(f, "{:>2} |", y + 1)?;
+            for x in 0..SIZE {
+                write!(f, " {:width$} |", cells[x + y * ROW_LEN])?;
+            }
+            f.write_str("  ")?;
+            map(f, walls, y)?;
+            write!(f, "{:w$}", "", w = map_width + 2 - SIZE)?;
+            map(f, caps, y)?;
+            writeln!(f)?;
+            writeln!(f, "{border}")?;
+        }
+
+        let cols = (b'a'..)
+            .take(SIZE)
+            .map(|col| format!(" {:^width$}  ", col as char))
+            .collect::<String>();
+        writeln!(f, "    {}", cols.trim_end())?;
+
+        for (name, color) in [("white", WHITE), ("black", BLACK)] {
+            writeln!(
+                f,
+                "{name}: {} stones, {} caps left",
+                s.stones_left[color], s.caps_left[color],
+            )?;
+        }
+
+        writeln!(
+            f,
+            "ply {}, {} to move",
+            s.ply,
+            if s.active_color() == WHITE {
+                "white"
+            } else {
+                "black"
+            },
+        )?;
+        writeln!(f, "hash {:016x}", s.hashes[s.ply].raw())?;
+        write!(
+            f,
+            "static eval {}",
+            Eval::new(s.eval_features().weigh(&s.eval)),
+        )
+    }
+}
@@ -0,0 +1,155 @@
+use crate::*;
+
+const MAGIC: &[u8; 6] = b"CATKPS";
+const VERSION: u8 = 1;
+const HEADER_LEN: usize = 8;
+
+const BITBOARD_LEN: usize = core::mem::size_of::<Bitboard>();
+
+/// Reads little-endian integers from the front of a byte slice.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let (bytes, rest) = self.0.split_at_checked(len)?;
+        self.0 = rest;
+        Some(bytes)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.bytes(1)?[0])
+    }
+
+    fn bitboard(&mut self) -> Option<Bitboard> {
+        Some(Bitboard::from_le_bytes(
+            self.bytes(BITBOARD_LEN)?.try_into().unwrap(),
+        ))
+    }
+
+    fn varint(&mut self) -> Option<u128> {
+        let mut value = 0u128;
+        for shift in (0..u128::BITS).step_by(7) {
+            let byte = self.u8()?;
+            value |= ((byte & 0x7f) as u128).checked_shl(shift)?;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+}
+
+fn write_varint(data: &mut Vec<u8>, mut value: u128) {
+    while value >= 0x80 {
+        data.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    data.push(value as u8);
+}
+
+impl State {
+    /// Encodes the position into a compact binary format.
+    ///
+    /// After an 8-byte header (magic, version, board size) come the ply as a little-endian `u32`,
+    /// the stones and caps left for white and black as bytes, and the bitboards of occupied
+    /// squares, walls and caps. Then each occupied square in bitboard order has its raw [`Stack`]
+    /// bits as a LEB128 varint. The side to move follows from the ply.
+    // The casts are unnecessary iff Stack is backed by u128
+    #[allow(clippy::unnecessary_cast)]
+    pub fn encode(&self) -> Vec<u8> {
+        let road = self.road.white | self.road.black;
+        let block = self.block.white | self.block.black;
+        let occupied = road | block;
+
+        let mut data = Vec::with_capacity(HEADER_LEN + 8 + 3 * BITBOARD_LEN + 2 * SIZE * SIZE);
+
+        data.extend_from_slice(MAGIC);
+        data.push(VERSION);
+        data.push(SIZE as u8);
+
+        data.extend_from_slice(&self.ply.to_le_bytes());
+        for reserve in [self.stones_left, self.caps_left] {
+            data.push(reserve.white as u8);
+            data.push(reserve.black as u8);
+        }
+
+        for bitboard in [occupied, block & !road, block & road] {
+            data.extend_from_slice(&bitboard.to_le_bytes());
+        }
+
+        for sq in bit_squares(occupied) {
+            write_varint(&mut data, self.stacks[sq].raw() as u128);
+        }
+
+        data
+    }
+
+    /// Replaces the position with one produced by [`State::encode`].
+    ///
+    /// On failure the position is left unchanged.
+    // The conversion is useless iff Stack is backed by u128, and the board mask is empty on 8x8
+    #[allow(clippy::useless_conversion, clippy::bad_bit_mask)]
+    pub fn decode(&mut self, data: &[u8]) -> Option<()> {
+        let mut r = Reader(data);
+
+        let header = r.bytes(HEADER_LEN)?;
+        if &header[..6] != MAGIC || header[6] != VERSION || header[7] as usize != SIZE {
+            return None;
+        }
+
+        let ply = u32::from_le_bytes(r.bytes(4)?.try_into().unwrap());
+
+        let stones_left = Pair::new(r.u8()? as u32, r.u8()? as u32);
+        let caps_left = Pair::new(r.u8()? as u32, r.u8()? as u32);
+
+        let occupied = r.bitboard()?;
+        let wall = r.bitboard()?;
+        let cap = r.bitboard()?;
+        if occupied & !BOARD != 0 || (wall | cap) & !occupied != 0 || wall & cap != 0 {
+            return None;
+        }
+
+        let mut stacks = [Stack::EMPTY; ARR_LEN];
+        for sq in bit_squares(occupied) {
+            let raw = r.varint()?.try_into().ok()?;
+            if raw < 2 {
+                return None;
+            }
+            // SAFETY: `raw` is nonzero
+            stacks[sq] = unsafe { Stack::from_raw(raw) };
+        }
+
+        if !r.0.is_empty() {
+            return None;
+        }
+
+        let mut road = Pair::default();
+        let mut block = Pair::default();
+        for sq in bit_squares(occupied) {
+            let color = stacks[sq].top_unchecked();
+            if wall & sq.bit() == 0 {
+                road[color] |= sq.bit();
+            }
+            if (wall | cap) & sq.bit() != 0 {
+                block[color] |= sq.bit();
+            }
+        }
+
+        self.stacks = stacks;
+        self.road = road;
+        self.block = block;
+        self.stones_left = stones_left;
+        self.caps_left = caps_left;
+        self.ply = ply;
+        self.last_reversible = ply;
+
+        self.influence.white.clear_and_flood(self.road.white, false);
+        self.influence.black.clear_and_flood(self.road.black, false);
+        *self.hash_mut() = self.compute_hash();
+
+        #[cfg(feature = "nnue")]
+        self.refresh_accumulators();
+
+        Some(())
+    }
+}
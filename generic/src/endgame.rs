@@ -0,0 +1,144 @@
+use crate::*;
+
+impl State {
+    /// Whether the position is a flat race that [`State::solve_endgame`] may settle within `depth`
+    /// plies: the reserves or the empty squares are low, the game can run out of either within
+    /// `depth` plies, and neither side is one move away from a road.
+    pub(crate) fn is_flat_race(&self, depth: u32) -> bool {
+        let color = self.active_color();
+        let reserves = |color| self.stones_left[color] + self.caps_left[color];
+        let occupied = self.road.white | self.road.black | self.block.white | self.block.black;
+        let empty = (BOARD ^ occupied).count_ones();
+
+        if min(reserves(WHITE), reserves(BLACK)) > self.search.endgame_reserves
+            && empty > self.search.endgame_empty
+        {
+            return false;
+        }
+
+        // Fewest plies until one side places its last piece or the board fills up
+        let plies_left = (2 * reserves(color))
+            .saturating_sub(1)
+            .min(2 * reserves(!color))
+            .min(empty);
+        if plies_left > depth {
+            return false;
+        }
+
+        [WHITE, BLACK].into_iter().all(|color| {
+            let half = self.eval_half(color);
+            half.dist_horz.min(half.dist_vert) > 1
+        })
+    }
+
+    /// Searches for the exact result of the game within `depth` plies.
+    ///
+    /// Only finished games are scored, so flat wins and draws account for komi. Games still
+    /// running at the horizon score as the worst possible result for the side to move at the
+    /// root, and then as the best. If both searches agree, the result does not depend on the
+    /// horizon and is exact.
+    ///
+    /// Moves are ordered by the transposition table and the killers of the main search, but
+    /// nothing is stored until the result is known to be exact.
+    pub(crate) fn solve_endgame(&mut self, depth: u32) -> Option<(Eval, Action)> {
+        let mut bounds = [-Eval::MAX, Eval::MAX].into_iter().map(|horizon| {
+            let mut alpha = -Eval::MAX;
+            let mut best = None;
+
+            _ = self.for_ordered_actions(|s, action| {
+                let score = -s.with(true, action, |s| {
+                    s.search_exact(depth - 1, -Eval::MAX, -alpha, -horizon)
+                });
+
+                if s.abort.load(Relaxed) {
+                    best = None;
+                    return Break(());
+                }
+
+                if best.is_none() || score > alpha {
+                    alpha = score;
+                    best = Some((score, action));
+                }
+
+                Continue(())
+            });
+
+            best
+        });
+
+        let lower = bounds.next().unwrap()?;
+        let upper = bounds.next().unwrap()?;
+
+        (lower.0 == upper.0).then_some(lower)
+    }
+
+    fn search_exact(&mut self, depth: u32, mut alpha: Eval, beta: Eval, horizon: Eval) -> Eval {
+        self.count_node();
+        self.status(
+            (),
+            |_, s| {
+                if depth == 0 {
+                    return horizon;
+                }
+
+                let mut best_score = -Eval::MAX;
+
+                _ = s.for_ordered_actions(|s, action| {
+                    if s.abort.load(Relaxed) {
+                        return Break(());
+                    }
+
+                    let score = -s.with(true, action, |s| {
+                        s.search_exact(depth - 1, -beta, -alpha, -horizon)
+                    });
+
+                    if score > best_score {
+                        best_score = score;
+                        if score > alpha {
+                            alpha = score;
+                            if alpha >= beta {
+                                s.killers[s.ply] = action;
+                                return Break(());
+                            }
+                        }
+                    }
+
+                    Continue(())
+                });
+
+                best_score
+            },
+            |_, _| Eval::ZERO,
+            |_, s| Eval::win(s.ply),
+            |_, s| Eval::loss(s.ply),
+        )
+    }
+
+    /// Calls `f` on the move from the transposition table, then the killer, then the rest.
+    fn for_ordered_actions(
+        &mut self,
+        mut f: impl FnMut(&mut Self, Action) -> ControlFlow<()>,
+    ) -> ControlFlow<()> {
+        let (idx, sig) = self.hash_mut().split(self.tt.len());
+        let tt_action = self.tt[idx]
+            .entry(sig)
+            .map_or(Action::PASS, |entry| entry.action);
+
+        if self.is_legal(tt_action) {
+            f(self, tt_action)?;
+        }
+
+        let killer = self.killers[self.ply];
+        if killer != tt_action && self.is_legal(killer) {
+            f(self, killer)?;
+        }
+
+        self.for_actions((), |_, s, action| {
+            if action == tt_action || action == killer {
+                Continue(())
+            } else {
+                f(s, action)
+            }
+        })
+    }
+}
@@ -6,20 +6,37 @@
 )]
 
 mod action;
+mod board;
+mod encoding;
+mod endgame;
 mod influence;
 mod lut;
+#[cfg(feature = "nnue")]
+mod nnue;
 mod params;
+mod playtak;
+mod pns;
 mod pv;
 mod square;
 mod state;
+mod symmetry;
+mod tablebase;
+mod tps;
+mod trace;
 mod tt;
 mod util;
 
 extern crate alloc;
 
+pub use params::SIZE_EVAL_PARAMS;
 pub use state::State;
 
-use crate::{action::*, influence::*, lut::*, params::*, pv::*, square::*, tt::*, util::*};
+#[cfg(feature = "nnue")]
+use crate::nnue::*;
+use crate::{
+    action::*, board::*, influence::*, lut::*, params::*, playtak::*, pv::*, square::*,
+    tablebase::*, tps::*, trace::*, tt::*, util::*,
+};
 
 use common::{
     game::*,
@@ -0,0 +1,314 @@
+use crate::*;
+
+const MAGIC: &[u8; 6] = b"CATKNN";
+const VERSION: u8 = 1;
+const HEADER_LEN: usize = 16;
+
+pub const HIDDEN: usize = 128;
+
+/// Top piece (3 kinds for each color), then the color of each of the pieces below it.
+const BELOW_TOP: usize = 2;
+const PER_SQUARE: usize = 6 + 2 * BELOW_TOP;
+pub const INPUTS: usize = SIZE * SIZE * PER_SQUARE;
+
+const QA: i32 = 255;
+const QB: i32 = 64;
+const SCALE: i32 = 400;
+
+/// Hidden layer values for one perspective.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C, align(32))]
+pub struct Accumulator([i16; HIDDEN]);
+
+impl Default for Accumulator {
+    fn default() -> Self {
+        Self([0; HIDDEN])
+    }
+}
+
+impl Accumulator {
+    #[inline]
+    fn add(&mut self, weights: &[i16; HIDDEN]) {
+        for (a, w) in self.0.iter_mut().zip(weights) {
+            *a = a.wrapping_add(*w);
+        }
+    }
+
+    #[inline]
+    fn sub(&mut self, weights: &[i16; HIDDEN]) {
+        for (a, w) in self.0.iter_mut().zip(weights) {
+            *a = a.wrapping_sub(*w);
+        }
+    }
+}
+
+/// A single hidden layer network over both perspectives, in the style of NNUE.
+///
+/// The file format is a 16-byte header (magic, version, board size, hidden layer size) followed
+/// by little-endian `i16` feature weights (`INPUTS` rows of `HIDDEN`), feature biases, output
+/// weights for the side to move and the opponent, and finally an `i32` output bias.
+pub struct Network {
+    feature_weights: Box<[[i16; HIDDEN]]>,
+    feature_bias: [i16; HIDDEN],
+    output_weights: Pair<[i16; HIDDEN]>,
+    output_bias: i32,
+}
+
+impl Network {
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        let (header, body) = data.split_at_checked(HEADER_LEN)?;
+
+        if &header[..6] != MAGIC
+            || header[6] != VERSION
+            || header[7] as usize != SIZE
+            || u64::from_le_bytes(header[8..].try_into().unwrap()) != HIDDEN as u64
+        {
+            return None;
+        }
+
+        let (body, bias) = body.split_at_checked(body.len().checked_sub(4)?)?;
+        if body.len() != (INPUTS + 3) * HIDDEN * 2 {
+            return None;
+        }
+
+        let mut rows = body.chunks_exact(HIDDEN * 2).map(|row| {
+            let mut out = [0; HIDDEN];
+            for (out, bytes) in out.iter_mut().zip(row.chunks_exact(2)) {
+                *out = i16::from_le_bytes([bytes[0], bytes[1]]);
+            }
+            out
+        });
+
+        Some(Self {
+            feature_weights: rows.by_ref().take(INPUTS).collect(),
+            feature_bias: rows.next()?,
+            output_weights: Pair::new(rows.next()?, rows.next()?),
+            output_bias: i32::from_le_bytes(bias.try_into().unwrap()),
+        })
+    }
+
+    /// Evaluates from the point of view of `color`.
+    pub fn evaluate(&self, accumulators: &Pair<Accumulator>, color: bool) -> i32 {
+        let (us, them) = accumulators.get_ref(color);
+
+        let sum = output(&us.0, &self.output_weights.white)
+            + output(&them.0, &self.output_weights.black)
+            + self.output_bias;
+
+        (sum as i64 * SCALE as i64 / (QA * QB) as i64) as i32
+    }
+}
+
+/// Dot product of the clipped hidden layer with the output weights.
+#[cfg(all(target_arch = "x86_64", target_feature = "avx2"))]
+#[inline]
+fn output(hidden: &[i16; HIDDEN], weights: &[i16; HIDDEN]) -> i32 {
+    use core::arch::x86_64::*;
+
+    const LANES: usize = 16;
+
+    // SAFETY: AVX2 is enabled at compile time, and every load stays within the arrays.
+    unsafe {
+        let zero = _mm256_setzero_si256();
+        let max = _mm256_set1_epi16(QA as i16);
+        let mut sum = _mm256_setzero_si256();
+
+        for i in (0..HIDDEN).step_by(LANES) {
+            let h = _mm256_loadu_si256(hidden.as_ptr().add(i).cast());
+            let w = _mm256_loadu_si256(weights.as_ptr().add(i).cast());
+            let h = _mm256_min_epi16(_mm256_max_epi16(h, zero), max);
+            sum = _mm256_add_epi32(sum, _mm256_madd_epi16(h, w));
+        }
+
+        let sum = _mm_add_epi32(
+            _mm256_castsi256_si128(sum),
+            _mm256_extracti128_si256(sum, 1),
+        );
+        let sum = _mm_add_epi32(sum, _mm_unpackhi_epi64(sum, sum));
+        let sum = _mm_add_epi32(sum, _mm_shuffle_epi32(sum, 1));
+        _mm_cvtsi128_si32(sum)
+    }
+}
+
+#[cfg(not(all(target_arch = "x86_64", target_feature = "avx2")))]
+#[inline]
+fn output(hidden: &[i16; HIDDEN], weights: &[i16; HIDDEN]) -> i32 {
+    hidden
+        .iter()
+        .zip(weights)
+        .map(|(&h, &w)| (h as i32).clamp(0, QA) * w as i32)
+        .sum()
+}
+
+/// Calls `f` with the index of each input feature of the square, from the point of view of
+/// `perspective`.
+#[inline]
+fn square_features(
+    stacks: &[Stack; ARR_LEN],
+    road: Bitboard,
+    block: Bitboard,
+    sq: Square,
+    perspective: bool,
+    mut f: impl FnMut(usize),
+) {
+    let stack = stacks[sq];
+    let Some(top) = stack.top() else {
+        return;
+    };
+
+    let base = (sq.col() + sq.row() * SIZE) * PER_SQUARE;
+    let kind = match (road & sq.bit() != 0, block & sq.bit() != 0) {
+        (true, false) => 0,
+        (false, true) => 1,
+        _ => 2,
+    };
+
+    f(base + (top != perspective) as usize * 3 + kind);
+
+    for depth in 1..stack.height().min(BELOW_TOP as u32 + 1) {
+        let color = stack.raw() >> depth & 1 != 0;
+        f(base + 6 + (depth as usize - 1) * 2 + (color != perspective) as usize);
+    }
+}
+
+pub struct Nnue {
+    pub network: Option<Box<Network>>,
+    pub accumulators: WrappingArray<Pair<Accumulator>, MAX_DEPTH>,
+}
+
+impl Default for Nnue {
+    fn default() -> Self {
+        Self {
+            network: None,
+            accumulators: WrappingArray([Pair::default(); MAX_DEPTH]),
+        }
+    }
+}
+
+impl fmt::Debug for Nnue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Nnue(loaded: {})", self.network.is_some())
+    }
+}
+
+impl State {
+    /// Computes the accumulators of the current position from scratch.
+    pub(crate) fn compute_accumulators(&self, network: &Network) -> Pair<Accumulator> {
+        let road = self.road.white | self.road.black;
+        let block = self.block.white | self.block.black;
+
+        Pair::new(WHITE, BLACK).map(|perspective| {
+            let mut acc = Accumulator(network.feature_bias);
+            for sq in bit_squares(road | block) {
+                square_features(&self.stacks, road, block, sq, perspective, |i| {
+                    acc.add(&network.feature_weights[i])
+                });
+            }
+            acc
+        })
+    }
+
+    pub(crate) fn refresh_accumulators(&mut self) {
+        if let Some(network) = &self.nnue.network {
+            self.nnue.accumulators[self.ply] = self.compute_accumulators(network);
+        }
+    }
+
+    /// Carries the accumulators of the previous ply over to the current one.
+    #[inline]
+    pub(crate) fn nnue_pass(&mut self) {
+        if self.nnue.network.is_some() {
+            self.nnue.accumulators[self.ply] = self.nnue.accumulators[self.ply - 1];
+        }
+    }
+
+    /// Updates the accumulators for a placement on `sq`, which was empty on the previous ply.
+    #[inline]
+    pub(crate) fn nnue_place(&mut self, sq: Square) {
+        let Some(network) = &self.nnue.network else {
+            return;
+        };
+
+        let road = self.road.white | self.road.black;
+        let block = self.block.white | self.block.black;
+
+        let mut accumulators = self.nnue.accumulators[self.ply - 1];
+        for perspective in [WHITE, BLACK] {
+            square_features(&self.stacks, road, block, sq, perspective, |i| {
+                accumulators[perspective].add(&network.feature_weights[i])
+            });
+        }
+        self.nnue.accumulators[self.ply] = accumulators;
+    }
+
+    /// Updates the accumulators for a spread from `src` to `end`, given the pieces of the
+    /// previous ply.
+    #[inline]
+    pub(crate) fn nnue_spread(
+        &mut self,
+        stacks: &[Stack; ARR_LEN],
+        road: Pair<Bitboard>,
+        block: Pair<Bitboard>,
+        src: Square,
+        end: Square,
+        dir: Direction,
+    ) {
+        let Some(network) = &self.nnue.network else {
+            return;
+        };
+
+        let (old_road, old_block) = (road.white | road.black, block.white | block.black);
+        let new_road = self.road.white | self.road.black;
+        let new_block = self.block.white | self.block.black;
+
+        let mut accumulators = self.nnue.accumulators[self.ply - 1];
+        for perspective in [WHITE, BLACK] {
+            let acc = &mut accumulators[perspective];
+
+            let mut sq = src;
+            loop {
+                square_features(stacks, old_road, old_block, sq, perspective, |i| {
+                    acc.sub(&network.feature_weights[i])
+                });
+                square_features(&self.stacks, new_road, new_block, sq, perspective, |i| {
+                    acc.add(&network.feature_weights[i])
+                });
+
+                if sq == end {
+                    break;
+                }
+                sq = sq.shift(1, dir);
+            }
+        }
+        self.nnue.accumulators[self.ply] = accumulators;
+    }
+
+    pub(crate) fn nnue_eval(&self) -> Option<Eval> {
+        let network = self.nnue.network.as_ref()?;
+        let score = network.evaluate(&self.nnue.accumulators[self.ply], self.active_color());
+        let max = Eval::DECISIVE.raw() - 1;
+
+        Some(Eval::new(score.clamp(-max, max)))
+    }
+}
+
+#[cfg(test)]
+pub fn random_network_bytes(seed: u64) -> Vec<u8> {
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha20Rng;
+
+    let mut rng = ChaCha20Rng::seed_from_u64(seed);
+
+    let mut data = Vec::new();
+    data.extend_from_slice(MAGIC);
+    data.push(VERSION);
+    data.push(SIZE as u8);
+    data.extend_from_slice(&(HIDDEN as u64).to_le_bytes());
+
+    for _ in 0..(INPUTS + 3) * HIDDEN {
+        data.extend_from_slice(&rng.gen_range(-64i16..64).to_le_bytes());
+    }
+    data.extend_from_slice(&rng.gen_range(-1000i32..1000).to_le_bytes());
+
+    data
+}
@@ -5,29 +5,6 @@ use crate::*;
 pub use inner::*;
 pub use provider::*;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct EvalParams {
-    pub max_dist_offset: i32,
-
-    pub flat_count: i32,
-    pub stones_left: i32,
-    pub caps_left: i32,
-    pub total_dist: i32,
-    pub smallest_dist: i32,
-    pub side_to_move: i32,
-}
-
-pub static EVAL_PARAMS: EvalParams = EvalParams {
-    max_dist_offset: -1,
-
-    flat_count: 10,
-    stones_left: -7,
-    caps_left: -15,
-    total_dist: -1,
-    smallest_dist: -2,
-    side_to_move: 21,
-};
-
 #[cfg(feature = "runtime-config")]
 mod provider {
     use super::*;
@@ -93,7 +70,7 @@ mod provider {
 
     impl EvalParamsProvider {
         pub fn new(params: EvalParams) -> Option<Self> {
-            (params == EVAL_PARAMS).then_some(Self)
+            (params == SIZE_EVAL_PARAMS).then_some(Self)
         }
     }
 
@@ -101,7 +78,7 @@ mod provider {
         type Target = EvalParams;
 
         fn deref(&self) -> &Self::Target {
-            &EVAL_PARAMS
+            &SIZE_EVAL_PARAMS
         }
     }
 }
@@ -118,6 +95,8 @@ mod inner {
     pub type Stack = Stack32;
     pub type ActionBacking = u16;
 
+    pub static SIZE_EVAL_PARAMS: EvalParams = EVAL_PARAMS;
+
     #[cfg(test)]
     pub const PERFT: &[(u32, u64)] = &[
         (1, 9),
@@ -142,6 +121,8 @@ mod inner {
     pub type Stack = Stack32;
     pub type ActionBacking = u16;
 
+    pub static SIZE_EVAL_PARAMS: EvalParams = EVAL_PARAMS;
+
     #[cfg(test)]
     pub const PERFT: &[(u32, u64)] = &[
         (1, 16),
@@ -165,6 +146,8 @@ mod inner {
     pub type Stack = Stack64;
     pub type ActionBacking = u16;
 
+    pub static SIZE_EVAL_PARAMS: EvalParams = EVAL_PARAMS;
+
     #[cfg(test)]
     pub const PERFT: &[(u32, u64)] = &[
         (1, 25),
@@ -188,6 +171,8 @@ mod inner {
     pub type Stack = Stack64;
     pub type ActionBacking = u16;
 
+    pub static SIZE_EVAL_PARAMS: EvalParams = EVAL_PARAMS;
+
     #[cfg(test)]
     pub const PERFT: &[(u32, u64)] = &[
         (1, 36),
@@ -211,6 +196,8 @@ mod inner {
     pub type Stack = Stack128;
     pub type ActionBacking = u16;
 
+    pub static SIZE_EVAL_PARAMS: EvalParams = EVAL_PARAMS;
+
     #[cfg(test)]
     pub const PERFT: &[(u32, u64)] = &[
         (1, 49),
@@ -233,6 +220,8 @@ mod inner {
     pub type Stack = Stack128;
     pub type ActionBacking = u16;
 
+    pub static SIZE_EVAL_PARAMS: EvalParams = EVAL_PARAMS;
+
     #[cfg(test)]
     pub const PERFT: &[(u32, u64)] = &[
         (1, 64),
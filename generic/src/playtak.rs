@@ -0,0 +1,125 @@
+use crate::*;
+
+/// Writes an action in PlayTak server notation, such as `P C3 W` or `M A1 A4 2 1`.
+pub struct PlayTakWriter(Action);
+
+impl PlayTakWriter {
+    pub fn new(action: Action) -> Self {
+        Self(action)
+    }
+}
+
+struct UpperSquare(Square);
+
+impl fmt::Display for UpperSquare {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}{}",
+            (b'A' + self.0.col() as u8) as char,
+            self.0.row() + 1
+        )
+    }
+}
+
+impl fmt::Display for PlayTakWriter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.branch(
+            f,
+            |f| f.write_str("<pass>"),
+            |f, sq, piece| {
+                write!(f, "P {}", UpperSquare(sq))?;
+                match piece {
+                    Flat => Ok(()),
+                    Wall => f.write_str(" W"),
+                    Cap => f.write_str(" C"),
+                }
+            },
+            |f, sq, dir, pat| {
+                let (_, counts) = pat.execute();
+                let end_sq = sq.shift(counts.count(), dir);
+                write!(f, "M {} {}", UpperSquare(sq), UpperSquare(end_sq))?;
+                counts
+                    .into_iter()
+                    .try_for_each(|count| write!(f, " {count}"))
+            },
+        )
+    }
+}
+
+fn parse_square(s: &str) -> Option<Square> {
+    let &[col, ref row @ ..] = s.as_bytes() else {
+        return None;
+    };
+
+    let col = col.to_ascii_lowercase().checked_sub(b'a')? as usize;
+    let row = core::str::from_utf8(row)
+        .ok()?
+        .parse::<usize>()
+        .ok()?
+        .checked_sub(1)?;
+    (col < SIZE && row < SIZE).then(|| sq(col + row * ROW_LEN))
+}
+
+impl Action {
+    /// Reads an action in PlayTak server notation.
+    ///
+    /// The drops of a spread are stored in a [`Pattern`] with a bit set after the pieces left
+    /// behind and after each drop but the last, which [`Pattern::execute`] reads back.
+    pub fn from_playtak(s: &str) -> Option<Self> {
+        let mut tokens = s.split_ascii_whitespace();
+
+        let action = match tokens.next()? {
+            "P" => {
+                let sq = parse_square(tokens.next()?)?;
+                let piece = match tokens.next() {
+                    None => Flat,
+                    Some("W") => Wall,
+                    Some("C") => Cap,
+                    Some(_) => return None,
+                };
+                Self::place(sq, piece)
+            }
+            "M" => {
+                let from = parse_square(tokens.next()?)?;
+                let to = parse_square(tokens.next()?)?;
+
+                let (dir, range) = if from.row() == to.row() && from.col() < to.col() {
+                    (Right, to.col() - from.col())
+                } else if from.row() == to.row() && from.col() > to.col() {
+                    (Left, from.col() - to.col())
+                } else if from.col() == to.col() && from.row() < to.row() {
+                    (Up, to.row() - from.row())
+                } else if from.col() == to.col() && from.row() > to.row() {
+                    (Down, from.row() - to.row())
+                } else {
+                    return None;
+                };
+
+                let mut drops = [0; SIZE];
+                let mut len = 0;
+                for token in tokens {
+                    *drops.get_mut(len)? = token.parse::<u32>().ok().filter(|&c| c != 0)?;
+                    len += 1;
+                }
+
+                let taken = drops.iter().sum::<u32>();
+                if len != range || taken > HAND {
+                    return None;
+                }
+
+                let mut bit = HAND - taken;
+                let mut pattern = 1 << bit;
+                for &count in &drops[..len - 1] {
+                    bit += count;
+                    pattern |= 1 << bit;
+                }
+
+                return Some(Self::spread(from, dir, pat(pattern)));
+            }
+            _ => return None,
+        };
+
+        tokens.next().is_none().then_some(action)
+    }
+}
@@ -0,0 +1,197 @@
+use crate::*;
+
+const PN_TT_SIZE: usize = 1 << 20;
+const INFINITY: u32 = u32::MAX / 2;
+
+// (phi, delta) from the point of view of the side to move
+const WON: (u32, u32) = (0, INFINITY);
+const LOST: (u32, u32) = (INFINITY, 0);
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PnEntry {
+    pub sig: u64,
+    pub phi: u32,
+    pub delta: u32,
+}
+
+/// Depth-first proof-number search.
+///
+/// Proves whether the attacker can force a win. Draws, repetitions and positions past the
+/// depth limit count in favor of the defender. Values cut off by a repetition depend on the path
+/// which led to the position, so they mark the result as truncated like the depth limit does.
+pub struct Pns {
+    tt: Box<[PnEntry]>,
+    attacker: bool,
+    root_ply: u32,
+    nodes: u64,
+    max_nodes: u64,
+    truncated: bool,
+    aborted: bool,
+}
+
+impl Pns {
+    pub fn new(max_nodes: u64) -> Self {
+        Self {
+            tt: vec![PnEntry::default(); PN_TT_SIZE].into_boxed_slice(),
+            attacker: WHITE,
+            root_ply: 0,
+            nodes: 0,
+            max_nodes,
+            truncated: false,
+            aborted: false,
+        }
+    }
+
+    /// Returns `Some(true)` if the attacker wins, `Some(false)` if the attacker cannot win,
+    /// and `None` if the budget ran out.
+    ///
+    /// A negative result is only trustworthy if [`Pns::is_truncated`] returns `false`.
+    pub fn run(&mut self, s: &mut State, attacker: bool) -> Option<bool> {
+        self.tt.fill(PnEntry::default());
+        self.attacker = attacker;
+        self.root_ply = s.ply;
+        self.nodes = 0;
+        self.truncated = false;
+        self.aborted = false;
+
+        let (phi, delta) = self.mid(s, INFINITY, INFINITY);
+        let root_wins = if s.active_color() == attacker {
+            phi == 0
+        } else {
+            delta == 0
+        };
+
+        (phi == 0 || delta == 0).then_some(root_wins)
+    }
+
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    fn terminal(&self, s: &mut State) -> Option<(u32, u32)> {
+        s.status(
+            (),
+            |_, _| None,
+            |_, s| Some(self.draw(s)),
+            |_, _| Some(WON),
+            |_, _| Some(LOST),
+        )
+    }
+
+    fn draw(&self, s: &State) -> (u32, u32) {
+        if s.active_color() == self.attacker {
+            LOST
+        } else {
+            WON
+        }
+    }
+
+    fn lookup(&self, hash: Hash) -> Option<(u32, u32)> {
+        let (idx, sig) = hash.split(self.tt.len());
+        let entry = self.tt[idx];
+        (entry.sig == sig).then_some((entry.phi, entry.delta))
+    }
+
+    fn store(&mut self, hash: Hash, (phi, delta): (u32, u32)) {
+        let (idx, sig) = hash.split(self.tt.len());
+        self.tt[idx] = PnEntry { sig, phi, delta };
+    }
+
+    fn mid(&mut self, s: &mut State, th_phi: u32, th_delta: u32) -> (u32, u32) {
+        self.nodes += 1;
+        s.nodes += 1;
+
+        if let Some(r) = self.terminal(s) {
+            return r;
+        }
+
+        if s.ply - self.root_ply >= MAX_DEPTH as u32 - 1 {
+            self.truncated = true;
+            return self.draw(s);
+        }
+
+        let hash = *s.hash_mut();
+
+        let mut children = s
+            .for_actions(Vec::new(), |mut acc, _, action| {
+                acc.push(action);
+                Continue(acc)
+            })
+            .into_continue()
+            .into_iter()
+            .map(|action| {
+                s.with(true, action, |s| {
+                    let hash = *s.hash_mut();
+                    let repeated = (self.root_ply..s.ply).any(|ply| s.hashes[ply] == hash);
+
+                    let value = if repeated {
+                        self.truncated = true;
+                        self.draw(s)
+                    } else {
+                        self.terminal(s)
+                            .or_else(|| self.lookup(hash))
+                            .unwrap_or((1, 1))
+                    };
+
+                    (action, value)
+                })
+            })
+            .collect::<Vec<_>>();
+
+        loop {
+            let mut phi = INFINITY;
+            let mut delta = 0u32;
+            let mut best = 0;
+            let mut second_delta = INFINITY;
+
+            for (i, &(_, (child_phi, child_delta))) in children.iter().enumerate() {
+                delta = delta.saturating_add(child_phi).min(INFINITY);
+
+                if child_delta < phi {
+                    second_delta = phi;
+                    phi = child_delta;
+                    best = i;
+                } else if child_delta < second_delta {
+                    second_delta = child_delta;
+                }
+            }
+
+            if phi >= th_phi || delta >= th_delta || self.aborted {
+                self.store(hash, (phi, delta));
+                return (phi, delta);
+            }
+
+            if self.nodes >= self.max_nodes || s.abort.load(Relaxed) {
+                self.aborted = true;
+                return (phi, delta);
+            }
+
+            let (action, (child_phi, _)) = children[best];
+            let child_th_phi = th_delta - delta + child_phi;
+            let child_th_delta = th_phi.min(second_delta.saturating_add(1));
+
+            children[best].1 = s.with(true, action, |s| self.mid(s, child_th_phi, child_th_delta));
+        }
+    }
+}
+
+impl State {
+    pub(crate) fn prove(&mut self, max_nodes: u64) -> Proof {
+        let color = self.active_color();
+        let mut pns = Pns::new(max_nodes);
+
+        match pns.run(self, color) {
+            Some(true) => return Proof::Win,
+            Some(false) => {}
+            None => return Proof::Unknown,
+        }
+
+        let truncated = pns.is_truncated();
+
+        match pns.run(self, !color) {
+            Some(true) => Proof::Loss,
+            Some(false) if !truncated && !pns.is_truncated() => Proof::Draw,
+            _ => Proof::Unknown,
+        }
+    }
+}
@@ -1,5 +1,12 @@
 use crate::*;
 
+pub(crate) struct EvalHalf {
+    pub traversable_horz: Bitboard,
+    pub traversable_vert: Bitboard,
+    pub dist_horz: i32,
+    pub dist_vert: i32,
+}
+
 #[repr(C)]
 #[derive(Debug)]
 pub struct State {
@@ -8,8 +15,10 @@ pub struct State {
 
     pub(crate) stones_left: Pair<u32>,
     pub(crate) caps_left: Pair<u32>,
+    pub(crate) start_reserves: Pair<u32>,
 
     pub(crate) nodes: u64,
+    pub(crate) node_limit: u64,
     pub(crate) generation: u32,
 
     pub(crate) half_komi: i32,
@@ -27,6 +36,10 @@ pub struct State {
     pub(crate) killers: WrappingArray<Action, MAX_DEPTH>,
 
     pub(crate) tt: Box<[TtBucket]>,
+    pub(crate) eval_cache: Box<[EvalEntry]>,
+    pub(crate) tablebase: Option<Tablebase>,
+    #[cfg(feature = "nnue")]
+    pub(crate) nnue: Nnue,
 
     pub(crate) search: SearchParamsProvider,
     pub(crate) eval: EvalParamsProvider,
@@ -34,7 +47,10 @@ pub struct State {
 
 impl State {
     pub fn new(opt: Options) -> Result<Self, NewGameError> {
-        if !opt.params.tt_size.is_power_of_two() {
+        let eval_cache_size = opt.params.eval_cache_size;
+        if !opt.params.tt_size.is_power_of_two()
+            || !(eval_cache_size == 0 || eval_cache_size.is_power_of_two())
+        {
             return Err(NewGameError);
         }
 
@@ -45,7 +61,12 @@ impl State {
             block: Pair::default(),
             stones_left: opt.start_stones,
             caps_left: opt.start_caps,
+            start_reserves: Pair::new(
+                opt.start_stones.white + opt.start_caps.white,
+                opt.start_stones.black + opt.start_caps.black,
+            ),
             nodes: 0,
+            node_limit: u64::MAX,
             generation: 0,
             half_komi: opt.half_komi,
             ply: 0,
@@ -59,8 +80,13 @@ impl State {
             tt: std::iter::repeat_n(TtBucket::default(), opt.params.tt_size)
                 .collect::<Vec<_>>()
                 .into_boxed_slice(),
+            eval_cache: vec![EvalEntry::default(); eval_cache_size].into_boxed_slice(),
+            tablebase: None,
+            #[cfg(feature = "nnue")]
+            nnue: Nnue::default(),
             search: SearchParamsProvider::new(opt.params).ok_or(NewGameError)?,
-            eval: EvalParamsProvider::new(EVAL_PARAMS).ok_or(NewGameError)?,
+            eval: EvalParamsProvider::new(opt.eval.unwrap_or(SIZE_EVAL_PARAMS))
+                .ok_or(NewGameError)?,
         })
     }
 
@@ -71,10 +97,16 @@ impl State {
         mut beta: Eval,
         allow_nmp: bool,
     ) -> Eval {
-        self.nodes += 1;
+        self.count_node();
         self.status(
             (),
             |_, s| {
+                if let Some(tablebase) = &s.tablebase {
+                    if let Some(value) = tablebase.probe(s.hashes[s.ply]) {
+                        return value.eval(s.ply);
+                    }
+                }
+
                 if depth == 0 {
                     return s.eval();
                 }
@@ -230,64 +262,157 @@ impl State {
         )
     }
 
-    pub(crate) fn eval(&self) -> Eval {
-        let eval_half = |color| {
-            let inf = self.influence[color];
-            let (my_road, opp_road) = self.road.get(color);
-            let (my_block, opp_block) = self.block.get(color);
+    /// Static evaluation, cached by hash so that transpositions skip the road distances.
+    pub(crate) fn eval(&mut self) -> Eval {
+        #[cfg(feature = "nnue")]
+        if let Some(eval) = self.nnue_eval() {
+            return eval;
+        }
+
+        if self.eval_cache.is_empty() {
+            return Eval::new(self.eval_features().weigh(&self.eval));
+        }
 
-            let my_wall = !my_road & my_block;
-            let opp_piece = opp_road | opp_block;
-            // let opp_flat = opp_road & !opp_block;
+        let (idx, sig) = self.hash_mut().split(self.eval_cache.len());
+        let entry = self.eval_cache[idx];
+        if let Some(score) = entry.score.filter(|_| entry.sig == sig) {
+            return score;
+        }
 
-            let opp_supports = opp_piece | my_wall;
+        let score = Eval::new(self.eval_features().weigh(&self.eval));
+        self.eval_cache[idx] = EvalEntry {
+            sig,
+            score: Some(score),
+        };
+        score
+    }
 
-            let right = opp_supports.shift(Right);
-            let up = opp_supports.shift(Up);
-            let left = opp_supports.shift(Left);
-            let down = opp_supports.shift(Down);
+    /// Road-building terms of one color, which do not depend on the side to move.
+    #[inline]
+    pub(crate) fn eval_half(&self, color: bool) -> EvalHalf {
+        let inf = self.influence[color];
+        let (my_road, opp_road) = self.road.get(color);
+        let (my_block, opp_block) = self.block.get(color);
 
-            let edge_right = right | EDGE_LEFT;
-            let edge_up = up | EDGE_BOTTOM;
-            let edge_left = left | EDGE_RIGHT;
-            let edge_down = down | EDGE_TOP;
+        let my_wall = !my_road & my_block;
+        let opp_piece = opp_road | opp_block;
+        // let opp_flat = opp_road & !opp_block;
 
-            let prot_hard_horz = edge_right & edge_left;
-            let prot_hard_vert = edge_up & edge_down;
+        let opp_supports = opp_piece | my_wall;
 
-            let prot_soft_horz = edge_right | edge_left;
-            let prot_soft_vert = edge_up | edge_down;
+        let right = opp_supports.shift(Right);
+        let up = opp_supports.shift(Up);
+        let left = opp_supports.shift(Left);
+        let down = opp_supports.shift(Down);
 
-            let prot_soft_no_edge_horz = right | left;
-            let prot_soft_no_edge_vert = up | down;
+        let edge_right = right | EDGE_LEFT;
+        let edge_up = up | EDGE_BOTTOM;
+        let edge_left = left | EDGE_RIGHT;
+        let edge_down = down | EDGE_TOP;
 
-            let blocked_horz = prot_hard_vert | prot_soft_no_edge_horz & prot_soft_vert;
-            let blocked_vert = prot_hard_horz | prot_soft_no_edge_vert & prot_soft_horz;
+        let prot_hard_horz = edge_right & edge_left;
+        let prot_hard_vert = edge_up & edge_down;
 
-            let nontraversable = my_wall | opp_block;
+        let prot_soft_horz = edge_right | edge_left;
+        let prot_soft_vert = edge_up | edge_down;
 
-            let traversable_horz = BOARD ^ (nontraversable | opp_road & blocked_horz);
-            let traversable_vert = BOARD ^ (nontraversable | opp_road & blocked_vert);
+        let prot_soft_no_edge_horz = right | left;
+        let prot_soft_no_edge_vert = up | down;
 
-            let dist_horz = self.flood_distance(inf[LEFT], inf[RIGHT], traversable_horz, my_road);
-            let dist_vert = self.flood_distance(inf[BOTTOM], inf[TOP], traversable_vert, my_road);
+        let blocked_horz = prot_hard_vert | prot_soft_no_edge_horz & prot_soft_vert;
+        let blocked_vert = prot_hard_horz | prot_soft_no_edge_vert & prot_soft_horz;
 
-            let total_dist = dist_horz + dist_vert;
-            let smaller_dist = min(dist_horz, dist_vert);
+        let nontraversable = my_wall | opp_block;
 
-            self.stones_left[color] as i32 * self.eval.stones_left
-                + self.caps_left[color] as i32 * self.eval.caps_left
-                + total_dist * self.eval.total_dist
-                + smaller_dist * self.eval.smallest_dist
-        };
+        let traversable_horz = BOARD ^ (nontraversable | opp_road & blocked_horz);
+        let traversable_vert = BOARD ^ (nontraversable | opp_road & blocked_vert);
 
+        let dist_horz = self.flood_distance(inf[LEFT], inf[RIGHT], traversable_horz, my_road);
+        let dist_vert = self.flood_distance(inf[BOTTOM], inf[TOP], traversable_vert, my_road);
+
+        EvalHalf {
+            traversable_horz,
+            traversable_vert,
+            dist_horz,
+            dist_vert,
+        }
+    }
+
+    /// Terms of one side, before they are combined into the view of the side to move by
+    /// [`State::eval_features`]. The flat count does not include the komi.
+    pub(crate) fn color_features(&self, color: bool) -> EvalFeatures {
+        let half = self.eval_half(color);
+
+        let mut reserves = 0;
+        let mut captives = 0;
+        let mut cap_stack = 0;
+
+        let caps = self.road[color] & self.block[color];
+        for sq in bit_squares(self.road[color] | self.block[color]) {
+            let stack = self.stacks[sq];
+            let below = stack.height().min(HAND) - 1;
+
+            // `stack.raw() as u128` is unnecessary iff Stack is backed by u128
+            #[allow(clippy::unnecessary_cast)]
+            let black = (stack.raw() as u128 >> 1 & (1 << below) - 1).count_ones() as i32;
+            let (own, opp) = if color == WHITE {
+                (below as i32 - black, black)
+            } else {
+                (black, below as i32 - black)
+            };
+
+            reserves += own;
+            captives += opp;
+            if caps & sq.bit() != 0 {
+                cap_stack += below as i32;
+            }
+        }
+
+        EvalFeatures {
+            flat_count: (self.road[color] & !self.block[color]).count_ones() as i32,
+            stones_left: self.stones_left[color] as i32,
+            caps_left: self.caps_left[color] as i32,
+            total_dist: half.dist_horz + half.dist_vert,
+            smallest_dist: min(half.dist_horz, half.dist_vert),
+            reserves,
+            captives,
+            cap_stack,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn eval_features(&self) -> EvalFeatures {
         let color = self.active_color();
-        let sides = eval_half(color) - eval_half(!color);
-        Eval::new(
-            sides * 2
-                + self.half_flat_count_diff() * sign(color) * self.eval.flat_count
-                + self.eval.side_to_move,
-        )
+        let (my, opp) = (self.color_features(color), self.color_features(!color));
+
+        // Each side's terms are doubled to share a scale with the half flat count
+        EvalFeatures {
+            flat_count: self.half_flat_count_diff() * sign(color),
+            stones_left: (my.stones_left - opp.stones_left) * 2,
+            caps_left: (my.caps_left - opp.caps_left) * 2,
+            total_dist: (my.total_dist - opp.total_dist) * 2,
+            smallest_dist: (my.smallest_dist - opp.smallest_dist) * 2,
+            side_to_move: 1,
+            reserves: (my.reserves - opp.reserves) * 2,
+            captives: (my.captives - opp.captives) * 2,
+            cap_stack: (my.cap_stack - opp.cap_stack) * 2,
+            phase: self.phase(),
+        }
+    }
+
+    /// Interpolates from [`PHASE_MAX`] at the start of the game to 0 when either player runs out
+    /// of pieces or the board fills up, whichever is nearer.
+    pub(crate) fn phase(&self) -> i32 {
+        let reserves = [WHITE, BLACK].map(|color| {
+            let left = self.stones_left[color] + self.caps_left[color];
+            left as i32 * PHASE_MAX / self.start_reserves[color].max(1) as i32
+        });
+
+        let occupied = self.road.white | self.road.black | self.block.white | self.block.black;
+        let empty = (SIZE * SIZE) as i32 - occupied.count_ones() as i32;
+        let fill = empty * PHASE_MAX / (SIZE * SIZE) as i32;
+
+        reserves[0].min(reserves[1]).min(fill)
     }
 
     // Performance experiment: swap C and &mut Self.
@@ -411,6 +536,9 @@ impl State {
         let r = action.branch(
             (&mut s, hash, f),
             |(s, hash, f)| {
+                #[cfg(feature = "nnue")]
+                s.nnue_pass();
+
                 *s.hash_mut() = hash;
                 f(s)
             },
@@ -447,6 +575,9 @@ impl State {
 
                 hash ^= unsafe { HASH_STACK[sq][0][s.stacks[sq].raw() as usize] };
 
+                #[cfg(feature = "nnue")]
+                s.nnue_place(sq);
+
                 *s.hash_mut() = hash;
                 let r = f(s);
 
@@ -473,6 +604,8 @@ impl State {
                 r
             },
             |(s, mut hash, f), mut sq, dir, pat| {
+                #[cfg(feature = "nnue")]
+                let src = sq;
                 let mut bit = sq.bit();
 
                 let road = s.road;
@@ -567,6 +700,9 @@ impl State {
                     }
                 }
 
+                #[cfg(feature = "nnue")]
+                s.nnue_spread(&stacks, road, block, src, sq, dir);
+
                 *s.hash_mut() = hash;
                 let r = f(s);
 
@@ -591,46 +727,7 @@ impl State {
 
     /// Assumes that there exists at least one [`State`] for which the [`Action`] is valid.
     pub(crate) fn is_legal(&mut self, action: Action) -> bool {
-        let color = self.active_color();
-        let opening = self.is_opening();
-        let clever = action.branch(
-            (),
-            |_| false,
-            |_, sq, piece| {
-                (!opening || piece.is_flat())
-                    && self.stacks[sq].is_empty()
-                    && if piece.is_stone() {
-                        self.stones_left[color] != 0
-                    } else {
-                        self.caps_left[color] != 0
-                    }
-            },
-            |_, sq, dir, pat| {
-                !opening && {
-                    let (taken, counts) = pat.execute();
-                    self.stacks[sq].height() >= taken
-                        && self.stacks[sq].top_unchecked() == color
-                        && {
-                            let range = counts.count();
-                            let end_sq = sq.shift(range, dir);
-
-                            let span_exclusive = ray(sq, dir) & ray(end_sq, -dir);
-                            let span = span_exclusive | end_sq.bit();
-
-                            let block = self.block.white | self.block.black;
-
-                            // TODO: Investigate unwrap
-                            span & block == 0
-                                || span_exclusive & block == 0 && counts.last().unwrap() == 1 && {
-                                    let road = self.road.white | self.road.black;
-                                    let cap = road & block;
-
-                                    cap & end_sq.bit() == 0 && cap & sq.bit() != 0
-                                }
-                        }
-                }
-            },
-        );
+        let clever = self.check_legal(action).is_ok();
 
         debug_assert_eq!(
             self.for_actions((), |_, _, other| {
@@ -647,6 +744,57 @@ impl State {
         clever
     }
 
+    /// Returns why the action is illegal, if it is.
+    pub(crate) fn check_legal(&self, action: Action) -> Result<(), PlayMoveError> {
+        let color = self.active_color();
+        let opening = self.is_opening();
+        let check = |illegal, error| if illegal { Err(error) } else { Ok(()) };
+        action.branch(
+            (),
+            |_| Err(PlayMoveError::Pass),
+            |_, sq, piece| {
+                check(opening && !piece.is_flat(), PlayMoveError::Opening)?;
+                check(!self.stacks[sq].is_empty(), PlayMoveError::Occupied)?;
+                if piece.is_stone() {
+                    check(self.stones_left[color] == 0, PlayMoveError::NoStones)
+                } else {
+                    check(self.caps_left[color] == 0, PlayMoveError::NoCaps)
+                }
+            },
+            |_, sq, dir, pat| {
+                check(opening, PlayMoveError::Opening)?;
+                let (taken, counts) = pat.execute();
+                check(
+                    self.stacks[sq].top() != Some(color),
+                    PlayMoveError::NotControlled,
+                )?;
+                check(self.stacks[sq].height() < taken, PlayMoveError::CarryLimit)?;
+
+                // Spreads may be parsed with drops past the edge of the board
+                let range = counts.count();
+                check(
+                    range > ray(sq, dir).count_ones() as _,
+                    PlayMoveError::OffBoard,
+                )?;
+
+                let end_sq = sq.shift(range, dir);
+                let span_exclusive = ray(sq, dir) & ray(end_sq, -dir);
+                let span = span_exclusive | end_sq.bit();
+
+                let block = self.block.white | self.block.black;
+                let road = self.road.white | self.road.black;
+                let cap = road & block;
+
+                // TODO: Investigate unwrap
+                let flattens = span_exclusive & block == 0
+                    && counts.last().unwrap() == 1
+                    && cap & end_sq.bit() == 0
+                    && cap & sq.bit() != 0;
+                check(span & block != 0 && !flattens, PlayMoveError::Blocked)
+            },
+        )
+    }
+
     // Performance experiment: use a Status enum.
     // Results: mixed, try again later.
 
@@ -745,10 +893,81 @@ impl State {
             - self.half_komi
     }
 
+    /// Stores an exact result for the current position, overriding any existing entry.
+    pub(crate) fn store_exact(&mut self, depth: u32, score: Eval, action: Action) {
+        let (idx, sig) = self.hash_mut().split(self.tt.len());
+
+        let bucket = &mut self.tt[idx];
+        let entry = if let Some(entry) = bucket.entry(sig) {
+            entry
+        } else {
+            bucket.worst_entry(self.generation)
+        };
+
+        *entry = TtEntry {
+            sig,
+            score,
+            action,
+            depth: depth as _,
+            packed: Packed::default(),
+        };
+        entry.packed.set_generation(self.generation);
+    }
+
+    /// Counts a searched node, setting the abort flag once the node limit is reached.
+    #[inline]
+    pub(crate) fn count_node(&mut self) {
+        self.nodes += 1;
+        if self.nodes >= self.node_limit {
+            self.abort.store(true, Relaxed);
+        }
+    }
+
     #[inline]
     pub(crate) fn hash_mut(&mut self) -> &mut Hash {
         &mut self.hashes[self.ply]
     }
+
+    /// Computes the hash of the position from scratch, matching the incremental updates in
+    /// [`State::with`].
+    pub(crate) fn compute_hash(&self) -> Hash {
+        self.compute_transformed_hash(Symmetry::IDENTITY)
+    }
+
+    /// Computes the hash of the image of the position under `sym` from scratch.
+    pub(crate) fn compute_transformed_hash(&self, sym: Symmetry) -> Hash {
+        let mut hash = if self.active_color() == BLACK {
+            Hash::SIDE_TO_MOVE
+        } else {
+            Hash::ZERO
+        };
+
+        let road = self.road.white | self.road.black;
+        let block = self.block.white | self.block.black;
+
+        for sq in bit_squares(road | block) {
+            let image = sq.transform(sym);
+
+            let mut stack = self.stacks[sq];
+            while let Some(color) = stack.top() {
+                stack.take(1);
+                hash ^= unsafe {
+                    HASH_STACK[image][stack.height() as usize]
+                        [Stack::one_tall(color).raw() as usize]
+                };
+            }
+
+            if block & sq.bit() != 0 {
+                hash ^= if road & sq.bit() != 0 {
+                    unsafe { HASH_CAP[image] }
+                } else {
+                    unsafe { HASH_WALL[image] }
+                };
+            }
+        }
+
+        hash
+    }
 }
 
 impl Game for State {
@@ -759,6 +978,18 @@ impl Game for State {
         let (idx, sig) = self.hash_mut().split(self.tt.len());
 
         'skip_full_window: {
+            if let Some((score, action)) = self.probe_root() {
+                self.store_exact(depth, score, action);
+                break 'skip_full_window;
+            }
+
+            if self.is_flat_race(depth) {
+                if let Some((score, action)) = self.solve_endgame(depth) {
+                    self.store_exact(depth, score, action);
+                    break 'skip_full_window;
+                }
+            }
+
             if let Some(&mut TtEntry {
                 score: expected_score,
                 packed,
@@ -827,6 +1058,60 @@ impl Game for State {
         }
     }
 
+    fn eval_features(&self) -> EvalFeatures {
+        self.eval_features()
+    }
+
+    fn result(&mut self) -> Option<GameResult> {
+        let color = self.active_color();
+        let decided = |s: &mut Self, winner| {
+            Some(if s.has_road(winner) {
+                GameResult::Road(winner)
+            } else {
+                GameResult::Flat(winner)
+            })
+        };
+
+        self.status(
+            (),
+            |_, _| None,
+            |_, _| Some(GameResult::Draw),
+            |_, s| decided(s, color),
+            |_, s| decided(s, !color),
+        )
+    }
+
+    fn prove(&mut self, max_nodes: u64) -> Proof {
+        self.prove(max_nodes)
+    }
+
+    fn solve(&mut self, max_positions: usize) -> Result<Vec<u8>, TablebaseError> {
+        self.solve(max_positions)
+            .map(|tablebase| tablebase.to_bytes())
+    }
+
+    fn set_tablebase(&mut self, data: &[u8]) -> Result<(), TablebaseError> {
+        let tablebase = Tablebase::from_bytes(data).ok_or(TablebaseError::Malformed)?;
+        if !tablebase.matches(self) {
+            return Err(TablebaseError::Mismatch);
+        }
+
+        self.tablebase = Some(tablebase);
+        Ok(())
+    }
+
+    #[cfg(feature = "nnue")]
+    fn set_network(&mut self, data: &[u8]) -> Result<(), NetworkError> {
+        self.nnue.network = Some(Box::new(Network::from_bytes(data).ok_or(NetworkError)?));
+        self.refresh_accumulators();
+        Ok(())
+    }
+
+    #[cfg(not(feature = "nnue"))]
+    fn set_network(&mut self, _data: &[u8]) -> Result<(), NetworkError> {
+        Err(NetworkError)
+    }
+
     fn parser(&mut self) -> fn(&str) -> Result<Box<dyn Move>, ParseMoveError> {
         |mv| {
             // TODO: Remove
@@ -863,18 +1148,58 @@ impl Game for State {
         }
     }
 
+    fn playtak_parser(&mut self) -> fn(&str) -> Result<Box<dyn Move>, ParseMoveError> {
+        |mv| match Action::from_playtak(mv) {
+            Some(action) => Ok(Box::new(action)),
+            None => Err(ParseMoveError),
+        }
+    }
+
+    fn playtak_move(&self, action: Box<dyn Move>) -> Box<dyn fmt::Display> {
+        let action = action.as_any();
+        let Some(&action) = action.downcast_ref::<Action>() else {
+            panic!("action-state size mismatch");
+        };
+
+        Box::new(PlayTakWriter::new(action))
+    }
+
     fn play(&mut self, action: Box<dyn Move>) -> Result<(), PlayMoveError> {
         let action = action.as_any();
         let Some(&action) = action.downcast_ref() else {
             panic!("action-state size mismatch");
         };
 
-        if self.is_legal(action) {
-            self.with(false, action, |_| ());
-            Ok(())
-        } else {
-            Err(PlayMoveError)
-        }
+        self.check_legal(action)?;
+        self.with(false, action, |_| ());
+        Ok(())
+    }
+
+    fn is_legal(&mut self, action: Box<dyn Move>) -> bool {
+        let action = action.as_any();
+        let Some(&action) = action.downcast_ref() else {
+            panic!("action-state size mismatch");
+        };
+
+        self.is_legal(action)
+    }
+
+    fn is_tactical(&self, action: Box<dyn Move>) -> bool {
+        let action = action.as_any();
+        let Some(&action) = action.downcast_ref::<Action>() else {
+            panic!("action-state size mismatch");
+        };
+
+        action.branch(
+            self,
+            |_| false,
+            |_, _, _| false,
+            |s, sq, dir, pat| {
+                let (_, counts) = pat.execute();
+                let them = !s.active_color();
+                (1..=counts.count()).any(|i| s.stacks[sq.shift(i, dir)].top() == Some(them))
+            },
+        )
     }
 
     fn set_position(&mut self, tps: &str) -> Result<(), SetPositionError> {
@@ -920,14 +1245,55 @@ impl Game for State {
         self.influence.black.clear_and_flood(self.road.black, false);
 
         self.ply = tps.ply() as u32;
+        *self.hash_mut() = self.compute_hash();
+
+        #[cfg(feature = "nnue")]
+        self.refresh_accumulators();
 
         Ok(())
     }
 
+    fn encode_position(&self) -> Vec<u8> {
+        self.encode()
+    }
+
+    fn decode_position(&mut self, data: &[u8]) -> Result<(), SetPositionError> {
+        self.decode(data).ok_or(SetPositionError)
+    }
+
+    fn transform(&mut self, sym: Symmetry) {
+        self.transform(sym)
+    }
+
+    fn canonical_hash(&self) -> Hash {
+        self.canonical_hash()
+    }
+
+    fn transform_move(&self, action: Box<dyn Move>, sym: Symmetry) -> Box<dyn Move> {
+        let action = action.as_any();
+        let Some(&action) = action.downcast_ref::<Action>() else {
+            panic!("action-state size mismatch");
+        };
+
+        Box::new(action.transform(sym))
+    }
+
     fn pv(&mut self) -> Box<dyn fmt::Display + '_> {
         Box::new(Pv::new(self))
     }
 
+    fn eval_trace(&self) -> Box<dyn fmt::Display + '_> {
+        Box::new(EvalTrace::new(self))
+    }
+
+    fn tps(&self) -> Box<dyn fmt::Display + '_> {
+        Box::new(TpsWriter::new(self))
+    }
+
+    fn board(&self) -> Box<dyn fmt::Display + '_> {
+        Box::new(BoardWriter::new(self))
+    }
+
     fn abort_flag(&self) -> AbortFlag {
         AbortFlag::new(&self.abort)
     }
@@ -950,6 +1316,10 @@ impl Game for State {
         self.nodes = 0;
     }
 
+    fn set_node_limit(&mut self, limit: u64) {
+        self.node_limit = limit;
+    }
+
     fn hash(&mut self) -> Hash {
         *self.hash_mut()
     }
@@ -966,6 +1336,10 @@ impl Game for State {
         self.ply & 1 != 0
     }
 
+    fn ply(&self) -> u32 {
+        self.ply
+    }
+
     fn is_opening(&self) -> bool {
         self.ply < 2
     }
@@ -988,4 +1362,242 @@ mod tests {
             assert_eq!(State::default().perft(depth, PerftMode::Batch), expected);
         }
     }
+
+    /// Plays a deterministic game of varied moves, calling `f` after each one.
+    fn walk_game(step: usize, f: impl FnMut(&mut State)) {
+        walk_game_from(&mut State::default(), step, f);
+    }
+
+    fn walk_game_from(s: &mut State, step: usize, mut f: impl FnMut(&mut State)) {
+        for i in 0..100 {
+            if s.status((), |_, _| false, |_, _| true, |_, _| true, |_, _| true) {
+                break;
+            }
+
+            let count = s.for_actions(0, |n, _, _| Continue(n + 1)).into_continue();
+            let action = s
+                .for_actions(i * step % count, |n, _, action| {
+                    if n == 0 {
+                        Break(action)
+                    } else {
+                        Continue(n - 1)
+                    }
+                })
+                .break_value()
+                .unwrap();
+
+            s.with(false, action, |_| ());
+            f(s);
+        }
+    }
+
+    #[test]
+    fn hash_from_scratch() {
+        walk_game(7, |s| {
+            let hash = *s.hash_mut();
+            assert_eq!(hash, s.compute_hash(), "{}", s.tps());
+        });
+    }
+
+    fn small_state() -> State {
+        State::new(Options {
+            params: SearchParams {
+                tt_size: 1,
+                ..SEARCH_PARAMS
+            },
+            ..Options::default(SIZE).unwrap()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn tps_round_trip() {
+        walk_game(5, |s| {
+            let tps = s.tps().to_string();
+            let mut t = small_state();
+            t.set_position(&tps).unwrap();
+
+            assert_eq!(t.tps().to_string(), tps);
+            assert_eq!(*t.hash_mut(), *s.hash_mut(), "{tps}");
+            assert_eq!(t.stones_left, s.stones_left, "{tps}");
+            assert_eq!(t.caps_left, s.caps_left, "{tps}");
+        });
+    }
+
+    #[test]
+    fn eval_cache_matches_static_eval() {
+        let uncached = |s: &State| Eval::new(s.eval_features().weigh(&s.eval));
+
+        let mut s = State::default();
+        assert_eq!(*s.hash_mut(), Hash::ZERO);
+        for _ in 0..2 {
+            assert_eq!(s.eval(), uncached(&s));
+        }
+
+        walk_game(3, |s| {
+            for _ in 0..2 {
+                assert_eq!(s.eval(), uncached(s), "{}", s.tps());
+            }
+        });
+    }
+
+    #[test]
+    fn off_board_spreads_are_illegal() {
+        let mut s = small_state();
+        let empty = vec![format!("x{SIZE}"); SIZE - 1].join("/");
+        s.set_position(&format!("{empty}/1,x{} 1 3", SIZE - 1))
+            .unwrap();
+
+        let parse = s.parser();
+        for mv in ["a1<", "a1-", "2a1+11"] {
+            assert!(s.play(parse(mv).unwrap()).is_err(), "{mv}");
+        }
+        assert!(s.play(parse("a1+").unwrap()).is_ok());
+    }
+
+    #[test]
+    fn playtak_round_trip() {
+        walk_game(3, |s| {
+            _ = s.for_actions((), |_, s, action| -> ControlFlow<()> {
+                let server = PlayTakWriter::new(action).to_string();
+                assert_eq!(Action::from_playtak(&server), Some(action), "{server}");
+
+                let ptn = s.parser()(&action.to_string()).unwrap();
+                assert_eq!(s.playtak_move(ptn).to_string(), server);
+                Continue(())
+            });
+        });
+    }
+
+    #[test]
+    fn encoding_round_trip() {
+        walk_game(3, |s| {
+            let data = s.encode();
+            let mut t = small_state();
+            t.decode(&data).unwrap();
+
+            let tps = s.tps().to_string();
+            assert_eq!(t.tps().to_string(), tps);
+            assert_eq!(t.encode(), data, "{tps}");
+            assert_eq!(*t.hash_mut(), *s.hash_mut(), "{tps}");
+            assert_eq!((t.road, t.block), (s.road, s.block), "{tps}");
+            assert_eq!(t.stones_left, s.stones_left, "{tps}");
+            assert_eq!(t.caps_left, s.caps_left, "{tps}");
+
+            assert!(t.decode(&data[..data.len() - 1]).is_none());
+            assert!(t.decode(&[&data[..], &[0]].concat()).is_none());
+            assert_eq!(t.tps().to_string(), tps);
+        });
+    }
+
+    fn copy_state(s: &State) -> State {
+        let mut t = small_state();
+        t.decode(&s.encode()).unwrap();
+        t
+    }
+
+    fn action_names(s: &mut State) -> Vec<String> {
+        let mut names = s
+            .for_actions(Vec::new(), |mut v, _, action| {
+                v.push(action.to_string());
+                Continue(v)
+            })
+            .into_continue();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn perft_symmetric() {
+        let mut ply = 0;
+        walk_game(7, |s| {
+            ply += 1;
+            if ply % 8 != 0 {
+                return;
+            }
+
+            let expected = s.perft(2, PerftMode::Batch);
+            let canonical = s.canonical_hash();
+
+            for sym in Symmetry::ALL {
+                let mut t = copy_state(s);
+                t.transform(sym);
+
+                assert_eq!(t.perft(2, PerftMode::Batch), expected);
+                assert_eq!(t.canonical_hash(), canonical);
+
+                let mut images = s
+                    .for_actions(Vec::new(), |mut v, _, action| {
+                        v.push(action.transform(sym).to_string());
+                        Continue(v)
+                    })
+                    .into_continue();
+                images.sort();
+                assert_eq!(images, action_names(&mut t));
+
+                t.transform(sym.inverse());
+                assert_eq!(t.encode(), s.encode());
+            }
+        });
+    }
+
+    #[test]
+    fn transformed_actions_commute() {
+        walk_game(11, |s| {
+            let actions = s
+                .for_actions(Vec::new(), |mut v, _, action| {
+                    v.push(action);
+                    Continue(v)
+                })
+                .into_continue();
+
+            for sym in Symmetry::ALL {
+                let mut t = copy_state(s);
+                t.transform(sym);
+
+                for &action in actions.iter().step_by(5) {
+                    let expected = s.with(true, action, |s| s.compute_transformed_hash(sym));
+                    let hash = t.with(true, action.transform(sym), |t| *t.hash_mut());
+                    assert_eq!(hash, expected, "{action}");
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn phase_bounds() {
+        let mut s = State::default();
+        assert_eq!(s.phase(), PHASE_MAX);
+
+        let full = (0..SIZE)
+            .map(|y| {
+                let row = (0..SIZE).map(|x| ["1", "2"][(x + y) % 2]);
+                row.collect::<Vec<_>>().join(",")
+            })
+            .collect::<Vec<_>>()
+            .join("/");
+        s.set_position(&format!("{full} 1 {}", SIZE * SIZE / 2 + 1))
+            .unwrap();
+        assert_eq!(s.phase(), 0);
+    }
+
+    #[cfg(feature = "nnue")]
+    #[test]
+    fn nnue_incremental_matches_refresh() {
+        fn check(s: &State) {
+            let network = s.nnue.network.as_ref().unwrap();
+            assert_eq!(s.nnue.accumulators[s.ply], s.compute_accumulators(network));
+        }
+
+        let mut s = State::default();
+        s.set_network(&random_network_bytes(1)).unwrap();
+
+        walk_game_from(&mut s, 7, |s| {
+            check(s);
+            _ = s.for_actions((), |_, s, action| -> ControlFlow<()> {
+                s.with(true, action, |s| check(s));
+                Continue(())
+            });
+        });
+    }
 }
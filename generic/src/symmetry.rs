@@ -0,0 +1,71 @@
+use crate::*;
+
+impl Square {
+    #[must_use]
+    #[inline]
+    pub fn transform(self, sym: Symmetry) -> Self {
+        let (mut col, mut row) = (self.col(), self.row());
+        if sym.transposes() {
+            (col, row) = (row, col);
+        }
+        if sym.mirrors_cols() {
+            col = SIZE - 1 - col;
+        }
+        if sym.mirrors_rows() {
+            row = SIZE - 1 - row;
+        }
+
+        sq(col + row * ROW_LEN)
+    }
+}
+
+impl Action {
+    #[must_use]
+    #[inline]
+    pub fn transform(self, sym: Symmetry) -> Self {
+        self.branch(
+            (),
+            |_| Self::PASS,
+            |_, sq, piece| Self::place(sq.transform(sym), piece),
+            |_, sq, dir, pat| Self::spread(sq.transform(sym), sym.direction(dir), pat),
+        )
+    }
+}
+
+fn transform_bitboard(bitboard: Bitboard, sym: Symmetry) -> Bitboard {
+    bit_squares(bitboard).fold(0, |b, sq| b | sq.transform(sym).bit())
+}
+
+impl State {
+    /// Replaces the position with its image under `sym`.
+    ///
+    /// Hashes of earlier plies are not transformed, so repetitions across the transformation are
+    /// not detected.
+    pub fn transform(&mut self, sym: Symmetry) {
+        let mut stacks = [Stack::EMPTY; ARR_LEN];
+        for sq in bit_squares(BOARD) {
+            stacks[sq.transform(sym)] = self.stacks[sq];
+        }
+        self.stacks = stacks;
+
+        self.road = self.road.map(|b| transform_bitboard(b, sym));
+        self.block = self.block.map(|b| transform_bitboard(b, sym));
+        self.last_reversible = self.ply;
+
+        self.influence.white.clear_and_flood(self.road.white, false);
+        self.influence.black.clear_and_flood(self.road.black, false);
+        *self.hash_mut() = self.compute_hash();
+
+        #[cfg(feature = "nnue")]
+        self.refresh_accumulators();
+    }
+
+    /// Computes a hash which is the same for the position and all its symmetric images.
+    pub fn canonical_hash(&self) -> Hash {
+        Symmetry::ALL
+            .into_iter()
+            .map(|sym| self.compute_transformed_hash(sym))
+            .min_by_key(|hash| hash.raw())
+            .unwrap()
+    }
+}
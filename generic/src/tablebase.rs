@@ -0,0 +1,578 @@
+use crate::*;
+
+const MAGIC: &[u8; 6] = b"CATKTB";
+const VERSION: u8 = 2;
+const HEADER_LEN: usize = 24;
+const ENTRY_LEN: usize = 10;
+
+const DRAW: u16 = 0;
+const WIN: u16 = 1 << 14;
+const LOSS: u16 = 2 << 14;
+const DIST_MASK: u16 = WIN - 1;
+const UNKNOWN: u16 = u16::MAX;
+
+const DEDUP_BATCH: usize = 1 << 16;
+
+/// Game-theoretic value of a position for the side to move, with the distance in plies to the
+/// end of the game under optimal play.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TbValue(u16);
+
+impl TbValue {
+    #[inline]
+    pub fn win(dist: u32) -> Self {
+        assert!(dist <= DIST_MASK as u32, "distance to end out of range");
+        Self(WIN | dist as u16)
+    }
+
+    #[inline]
+    pub fn loss(dist: u32) -> Self {
+        assert!(dist <= DIST_MASK as u32, "distance to end out of range");
+        Self(LOSS | dist as u16)
+    }
+
+    #[inline]
+    pub fn is_win(self) -> bool {
+        self.0 & !DIST_MASK == WIN
+    }
+
+    #[inline]
+    pub fn is_loss(self) -> bool {
+        self.0 & !DIST_MASK == LOSS
+    }
+
+    #[inline]
+    pub fn dist(self) -> u32 {
+        (self.0 & DIST_MASK) as u32
+    }
+
+    /// Converts the value to a search score, given the ply of the probed position.
+    #[inline]
+    pub fn eval(self, ply: u32) -> Eval {
+        if self.is_win() {
+            Eval::win(ply + self.dist())
+        } else if self.is_loss() {
+            Eval::loss(ply + self.dist())
+        } else {
+            Eval::ZERO
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TbEntry {
+    sig: u64,
+    value: TbValue,
+}
+
+/// Maps position hashes to exact values, sorted by hash.
+///
+/// The file format is a 24-byte header (magic, version, board size, start stones and caps of
+/// each color, half komi, entry count) followed by little-endian `(hash: u64, value: u16)`
+/// entries. Values depend on the reserves and komi, so a table only applies to games with the
+/// same ones.
+pub struct Tablebase {
+    stones: Pair<u32>,
+    caps: Pair<u32>,
+    half_komi: i32,
+    entries: Box<[TbEntry]>,
+}
+
+impl fmt::Debug for Tablebase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Tablebase({} positions)", self.entries.len())
+    }
+}
+
+impl Tablebase {
+    #[inline]
+    pub fn probe(&self, hash: Hash) -> Option<TbValue> {
+        let sig = hash.raw();
+        self.entries
+            .binary_search_by_key(&sig, |e| e.sig)
+            .ok()
+            .map(|i| self.entries[i].value)
+    }
+
+    /// Whether the table was solved for the reserves and komi of the game.
+    pub fn matches(&self, s: &State) -> bool {
+        (self.stones, self.caps) == s.total_reserves() && self.half_komi == s.half_komi
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        let (header, body) = data.split_at_checked(HEADER_LEN)?;
+
+        if &header[..6] != MAGIC || header[6] != VERSION || header[7] as usize != SIZE {
+            return None;
+        }
+
+        let len = u64::from_le_bytes(header[16..].try_into().unwrap()) as usize;
+        if body.len() != len.checked_mul(ENTRY_LEN)? {
+            return None;
+        }
+
+        let entries = body
+            .chunks_exact(ENTRY_LEN)
+            .map(|chunk| TbEntry {
+                sig: u64::from_le_bytes(chunk[..8].try_into().unwrap()),
+                value: TbValue(u16::from_le_bytes(chunk[8..].try_into().unwrap())),
+            })
+            .collect::<Box<[_]>>();
+
+        entries
+            .windows(2)
+            .all(|w| w[0].sig < w[1].sig)
+            .then_some(Self {
+                stones: Pair::new(header[8] as u32, header[9] as u32),
+                caps: Pair::new(header[10] as u32, header[11] as u32),
+                half_komi: header[12] as i8 as i32,
+                entries,
+            })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(HEADER_LEN + self.entries.len() * ENTRY_LEN);
+
+        data.extend_from_slice(MAGIC);
+        data.push(VERSION);
+        data.push(SIZE as u8);
+        for reserves in [self.stones, self.caps] {
+            data.extend_from_slice(&[reserves.white as u8, reserves.black as u8]);
+        }
+        data.extend_from_slice(&[self.half_komi as i8 as u8, 0, 0, 0]);
+        data.extend_from_slice(&(self.entries.len() as u64).to_le_bytes());
+
+        for entry in self.entries.iter() {
+            data.extend_from_slice(&entry.sig.to_le_bytes());
+            data.extend_from_slice(&entry.value.0.to_le_bytes());
+        }
+
+        data
+    }
+}
+
+/// Merges two sorted slices of distinct positions.
+fn merge(a: &[u128], b: &[u128]) -> Vec<u128> {
+    let mut merged = Vec::with_capacity(a.len() + b.len());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] < b[j] {
+            merged.push(a[i]);
+            i += 1;
+        } else {
+            merged.push(b[j]);
+            j += 1;
+        }
+    }
+    merged.extend_from_slice(&a[i..]);
+    merged.extend_from_slice(&b[j..]);
+    merged
+}
+
+fn dedup(positions: &mut Vec<u128>) {
+    positions.sort_unstable();
+    positions.dedup();
+}
+
+/// Positions with the same number of pieces on the board, sorted, with their values.
+struct Layer {
+    positions: Vec<u128>,
+    values: Vec<u16>,
+}
+
+impl Layer {
+    fn value(&self, packed: u128) -> TbValue {
+        TbValue(self.values[self.positions.binary_search(&packed).unwrap()])
+    }
+}
+
+/// Exhaustive retrograde solver over all positions reachable from a root.
+///
+/// Placements add a piece to the board and spreads keep the count, so positions fall into layers
+/// by the number of pieces on the board. The layers are enumerated forwards from the root and
+/// solved backwards from the fullest, so that only the layer being solved and the one above it
+/// hold values. Positions are packed losslessly into a `u128` and indexed by their rank in the
+/// sorted layer, which limits solving to boards where the reserves are small enough (3x3 and
+/// 4x4 with reduced reserves).
+///
+/// Every layer enumerated is kept in memory, so this is not a strong solution of 3x3 Tak: from
+/// the empty board with the default reserves, enumeration passes 230 million positions, and each
+/// extra stone multiplies the count by about 40. Solving the full game would need the layers on
+/// disk or a perfect index of positions.
+struct Solver<'a> {
+    s: &'a mut State,
+    stones: Pair<u32>,
+    caps: Pair<u32>,
+}
+
+impl<'a> Solver<'a> {
+    fn new(s: &'a mut State) -> Self {
+        let (stones, caps) = s.total_reserves();
+        Self { s, stones, caps }
+    }
+
+    fn fits(&self) -> bool {
+        let pieces = self.stones.white + self.stones.black + self.caps.white + self.caps.black;
+
+        // Unary height, colors, wall and cap bits per square, and the ply class
+        2 * pieces + 3 * (SIZE * SIZE) as u32 + 2 <= u128::BITS
+    }
+
+    // `stack.raw() as u128` is unnecessary iff Stack is backed by u128
+    #[allow(clippy::unnecessary_cast)]
+    fn pack(s: &State) -> u128 {
+        let cap = s.block.white & s.road.white | s.block.black & s.road.black;
+        let wall = (s.block.white | s.block.black) & !cap;
+
+        let mut packed = 0u128;
+        let mut len = 0;
+        for sq in bit_squares(BOARD) {
+            let stack = s.stacks[sq];
+            let height = stack.height();
+
+            packed |= ((1 << height) - 1) << len;
+            len += height + 1;
+
+            if height > 0 {
+                packed |= (stack.raw() as u128 & (1 << height) - 1) << len;
+                len += height;
+
+                packed |=
+                    ((wall & sq.bit() != 0) as u128 | ((cap & sq.bit() != 0) as u128) << 1) << len;
+                len += 2;
+            }
+        }
+
+        debug_assert!(len <= u128::BITS - 2);
+
+        // Only the first two plies behave differently
+        packed | (s.ply.min(2 | s.ply & 1) as u128) << u128::BITS - 2
+    }
+
+    fn unpack(&mut self, mut packed: u128) {
+        let s = &mut *self.s;
+
+        s.road = Pair::default();
+        s.block = Pair::default();
+        s.stones_left = self.stones;
+        s.caps_left = self.caps;
+        s.ply = (packed >> u128::BITS - 2) as u32;
+
+        for sq in bit_squares(BOARD) {
+            let height = packed.trailing_ones();
+            packed >>= height + 1;
+
+            s.stacks[sq] = Stack::EMPTY;
+            if height == 0 {
+                continue;
+            }
+
+            let colors = packed & (1 << height) - 1;
+            packed >>= height;
+
+            for j in (0..height).rev() {
+                let color = colors >> j & 1 != 0;
+                s.stacks[sq].drop(&mut Hand::one_piece(color), 1);
+                s.stones_left[color] -= 1;
+            }
+
+            let color = s.stacks[sq].top_unchecked();
+            let (is_wall, is_cap) = (packed & 1 != 0, packed & 2 != 0);
+            packed >>= 2;
+
+            if !is_wall {
+                s.road[color] |= sq.bit();
+            }
+            if is_wall || is_cap {
+                s.block[color] |= sq.bit();
+            }
+            if is_cap {
+                s.stones_left[color] += 1;
+                s.caps_left[color] -= 1;
+            }
+        }
+
+        s.influence.white.clear_and_flood(s.road.white, false);
+        s.influence.black.clear_and_flood(s.road.black, false);
+
+        *s.hash_mut() = s.compute_hash();
+    }
+
+    fn terminal(&mut self) -> Option<u16> {
+        self.s.status(
+            (),
+            |_, _| None,
+            |_, _| Some(DRAW),
+            |_, _| Some(TbValue::win(0).0),
+            |_, _| Some(TbValue::loss(0).0),
+        )
+    }
+
+    /// Appends the children of the position, split into those reached by a spread, which stay
+    /// in its layer, and those reached by a placement, which are in the layer above.
+    fn children(&mut self, spreads: &mut Vec<u128>, placements: &mut Vec<u128>) {
+        let actions = self
+            .s
+            .for_actions(Vec::new(), |mut acc, _, action| {
+                acc.push(action);
+                Continue(acc)
+            })
+            .into_continue();
+
+        for action in actions {
+            let packed = self.s.with(true, action, |s| Self::pack(s));
+            let is_place = action.branch((), |_| false, |_, _, _| true, |_, _, _, _| false);
+            if is_place {
+                placements.push(packed);
+            } else {
+                spreads.push(packed);
+            }
+        }
+    }
+
+    /// Collects the positions reachable from the root into sorted layers, starting with the
+    /// layer of the root. Returns [`None`] if more than `max_positions` are reachable.
+    fn enumerate(&mut self, root: u128, max_positions: usize) -> Option<Vec<Vec<u128>>> {
+        let mut layers = Vec::new();
+        let mut total = 0;
+        let mut seeds = vec![root];
+
+        while !seeds.is_empty() {
+            let mut layer = Vec::new();
+            let mut placements = Vec::new();
+            let mut frontier = seeds;
+
+            // Close the layer under spreads, one wave of new positions at a time
+            while !frontier.is_empty() {
+                total += frontier.len();
+                if total > max_positions {
+                    return None;
+                }
+                layer = merge(&layer, &frontier);
+
+                let mut spreads = Vec::new();
+                let mut unique = 0;
+                for &packed in &frontier {
+                    self.unpack(packed);
+                    if self.terminal().is_none() {
+                        self.children(&mut spreads, &mut placements);
+                    }
+
+                    // Most children are reached from many positions
+                    if spreads.len() + placements.len() > 2 * unique + DEDUP_BATCH {
+                        dedup(&mut spreads);
+                        dedup(&mut placements);
+                        unique = spreads.len() + placements.len();
+                    }
+                }
+
+                dedup(&mut spreads);
+                dedup(&mut placements);
+                spreads.retain(|packed| layer.binary_search(packed).is_err());
+                frontier = spreads;
+            }
+
+            layers.push(layer);
+            seeds = placements;
+        }
+
+        Some(layers)
+    }
+
+    /// Solves a layer given the solved layer above it, and returns its values.
+    fn retrograde(&mut self, positions: &[u128], above: Option<&Layer>) -> Vec<u16> {
+        let mut values = vec![UNKNOWN; positions.len()];
+        let mut unresolved = Vec::new();
+        for (i, &packed) in positions.iter().enumerate() {
+            self.unpack(packed);
+            match self.terminal() {
+                Some(value) => values[i] = value,
+                None => unresolved.push(i),
+            }
+        }
+
+        // Children in the layer above may be won or lost further from the end than any position
+        // of this layer resolved so far
+        let above_dist = above.map_or(0, |layer| {
+            layer
+                .values
+                .iter()
+                .map(|&v| TbValue(v).dist())
+                .max()
+                .unwrap_or(0)
+        });
+
+        let (mut spreads, mut placements) = (Vec::new(), Vec::new());
+        for dist in 1.. {
+            let mut resolved = false;
+
+            unresolved.retain(|&i| {
+                self.unpack(positions[i]);
+                spreads.clear();
+                placements.clear();
+                self.children(&mut spreads, &mut placements);
+
+                let children = spreads
+                    .iter()
+                    .map(|&child| TbValue(values[positions.binary_search(&child).unwrap()]))
+                    .chain(placements.iter().map(|&child| above.unwrap().value(child)));
+
+                let mut all_wins = true;
+                let mut longest_win = 0;
+                let mut value = UNKNOWN;
+
+                for child in children {
+                    if child.0 == UNKNOWN || child.0 == DRAW {
+                        all_wins = false;
+                    } else if child.is_loss() && child.dist() < dist {
+                        // Earlier passes resolve shorter wins
+                        debug_assert_eq!(child.dist(), dist - 1);
+                        value = TbValue::win(dist).0;
+                        break;
+                    } else if child.is_win() && child.dist() < dist {
+                        longest_win = longest_win.max(child.dist());
+                    } else {
+                        all_wins = false;
+                    }
+                }
+
+                if value == UNKNOWN && all_wins && longest_win == dist - 1 {
+                    value = TbValue::loss(dist).0;
+                }
+
+                values[i] = value;
+                resolved |= value != UNKNOWN;
+                value == UNKNOWN
+            });
+
+            if !resolved && dist > above_dist {
+                break;
+            }
+        }
+
+        for i in unresolved {
+            values[i] = DRAW;
+        }
+        values
+    }
+
+    fn entries(&mut self, layer: &Layer, entries: &mut Vec<TbEntry>) {
+        for (&packed, &value) in layer.positions.iter().zip(&layer.values) {
+            self.unpack(packed);
+            entries.push(TbEntry {
+                sig: self.s.hash_mut().raw(),
+                value: TbValue(value),
+            });
+        }
+    }
+}
+
+impl State {
+    /// Counts the stones and caps of each color in the game, both placed and in reserve.
+    pub(crate) fn total_reserves(&self) -> (Pair<u32>, Pair<u32>) {
+        let cap = self.block.white & self.road.white | self.block.black & self.road.black;
+
+        let mut stones = self.stones_left;
+        let mut caps = self.caps_left;
+        for sq in bit_squares(BOARD) {
+            let stack = self.stacks[sq];
+            for j in 0..stack.height() {
+                stones[stack.raw() >> j & 1 != 0] += 1;
+            }
+
+            if cap & sq.bit() != 0 {
+                stones[stack.top_unchecked()] -= 1;
+                caps[stack.top_unchecked()] += 1;
+            }
+        }
+
+        (stones, caps)
+    }
+
+    /// Solves every position reachable from the current one, or returns
+    /// [`TablebaseError::TooManyPositions`] once more than `max_positions` are reachable. See
+    /// [`Solver`] for the reserves this can handle.
+    pub(crate) fn solve(&mut self, max_positions: usize) -> Result<Tablebase, TablebaseError> {
+        let ply = self.ply;
+        let last_reversible = self.last_reversible;
+        let half_komi = self.half_komi;
+
+        let mut solver = Solver::new(self);
+        if !solver.fits() {
+            return Err(TablebaseError::TooManyPositions);
+        }
+        let (stones, caps) = (solver.stones, solver.caps);
+
+        let root = Solver::pack(solver.s);
+        let layers = solver.enumerate(root, max_positions);
+        let solved = layers.is_some();
+
+        let mut entries = Vec::new();
+        if let Some(layers) = layers {
+            entries.reserve_exact(layers.iter().map(Vec::len).sum());
+
+            let mut above = None::<Layer>;
+            for positions in layers.into_iter().rev() {
+                let values = solver.retrograde(&positions, above.as_ref());
+                if let Some(above) = &above {
+                    solver.entries(above, &mut entries);
+                }
+                above = Some(Layer { positions, values });
+            }
+            solver.entries(&above.unwrap(), &mut entries);
+        }
+
+        solver.unpack(root);
+        self.ply = ply;
+        self.last_reversible = last_reversible;
+        *self.hash_mut() = self.compute_hash();
+
+        if !solved {
+            return Err(TablebaseError::TooManyPositions);
+        }
+
+        entries.sort_unstable_by_key(|e| e.sig);
+        if entries.windows(2).any(|w| w[0].sig == w[1].sig) {
+            return Err(TablebaseError::HashCollision);
+        }
+
+        Ok(Tablebase {
+            stones,
+            caps,
+            half_komi,
+            entries: entries.into_boxed_slice(),
+        })
+    }
+
+    /// Picks the best move at the root according to the tablebase.
+    pub(crate) fn probe_root(&mut self) -> Option<(Eval, Action)> {
+        let tablebase = self.tablebase.take()?;
+
+        let best = self
+            .for_actions(None, |best: Option<(Eval, Action)>, s, action| {
+                let score = s.with(true, action, |s| {
+                    s.status(
+                        (),
+                        |_, s| {
+                            tablebase
+                                .probe(*s.hash_mut())
+                                .map(|value| -value.eval(s.ply))
+                        },
+                        |_, _| Some(Eval::ZERO),
+                        |_, s| Some(Eval::loss(s.ply)),
+                        |_, s| Some(Eval::win(s.ply)),
+                    )
+                });
+
+                match (score, best) {
+                    (None, _) => Break(None),
+                    (Some(score), Some((best_score, _))) if score <= best_score => Continue(best),
+                    (Some(score), _) => Continue(Some((score, action))),
+                }
+            })
+            .into_inner();
+
+        self.tablebase = Some(tablebase);
+        best
+    }
+}
@@ -0,0 +1,57 @@
+use crate::*;
+
+pub struct TpsWriter<'a>(&'a State);
+
+impl<'a> TpsWriter<'a> {
+    pub fn new(state: &'a State) -> Self {
+        Self(state)
+    }
+}
+
+impl<'a> fmt::Display for TpsWriter<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = self.0;
+        let road = s.road.white | s.road.black;
+        let block = s.block.white | s.block.black;
+
+        for y in (0..SIZE).rev() {
+            if y != SIZE - 1 {
+                f.write_str("/")?;
+            }
+
+            let mut x = 0;
+            while x < SIZE {
+                if x != 0 {
+                    f.write_str(",")?;
+                }
+
+                let empty = (x..SIZE)
+                    .take_while(|&x| s.stacks[sq(x + y * ROW_LEN)].is_empty())
+                    .count();
+                if empty != 0 {
+                    if empty == 1 {
+                        f.write_str("x")?;
+                    } else {
+                        write!(f, "x{empty}")?;
+                    }
+                    x += empty;
+                    continue;
+                }
+
+                let sq = sq(x + y * ROW_LEN);
+                let stack = s.stacks[sq];
+                for depth in (0..stack.height()).rev() {
+                    let color = stack.raw() >> depth & 1 != 0;
+                    f.write_str(if color == WHITE { "1" } else { "2" })?;
+                }
+
+                if block & sq.bit() != 0 {
+                    f.write_str(if road & sq.bit() != 0 { "C" } else { "S" })?;
+                }
+                x += 1;
+            }
+        }
+
+        write!(f, " {} {}", s.ply % 2 + 1, s.ply / 2 + 1)
+    }
+}
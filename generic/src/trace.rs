@@ -0,0 +1,136 @@
+use crate::*;
+
+const BOARD_WIDTH: usize = "horizontal".len();
+
+pub struct EvalTrace<'a>(&'a State);
+
+impl<'a> EvalTrace<'a> {
+    pub fn new(state: &'a State) -> Self {
+        Self(state)
+    }
+}
+
+fn color_name(color: bool) -> &'static str {
+    if color == WHITE {
+        "white"
+    } else {
+        "black"
+    }
+}
+
+impl<'a> fmt::Display for EvalTrace<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = self.0;
+
+        for color in [WHITE, BLACK] {
+            let half = s.eval_half(color);
+
+            writeln!(
+                f,
+                "{}: {} stones, {} caps left, road distance {} horizontal, {} vertical",
+                color_name(color),
+                s.stones_left[color],
+                s.caps_left[color],
+                half.dist_horz,
+                half.dist_vert,
+            )?;
+
+            writeln!(f, "  {:<w$}  vertical", "horizontal", w = BOARD_WIDTH)?;
+            let row = |f: &mut fmt::Formatter, board: Bitboard, y| {
+                (0..SIZE).try_for_each(|x| {
+                    let set = board >> (x + y * ROW_LEN) & 1 != 0;
+                    f.write_str(if set { "#" } else { "." })
+                })
+            };
+
+            for y in (0..SIZE).rev() {
+                f.write_str("  ")?;
+                row(f, half.traversable_horz, y)?;
+                write!(f, "{:w$}", "", w = BOARD_WIDTH + 2 - SIZE)?;
+                row(f, half.traversable_vert, y)?;
+                writeln!(f)?;
+            }
+        }
+
+        let features = s.eval_features();
+        let params = *s.eval;
+        let color = s.active_color();
+        let sides = Pair::new(s.color_features(WHITE), s.color_features(BLACK));
+
+        // Each side's terms are doubled to share a scale with the half flat count, as in
+        // `State::eval_features`, and the komi is counted in half flats for black
+        type Value = fn(&EvalFeatures) -> i32;
+        type Weight = fn(&EvalWeights) -> i32;
+        let terms: [(_, Pair<i32>, i32, Weight); 10] = {
+            let term = |value: Value| Pair::new(value(&sides.white), value(&sides.black));
+            [
+                ("flat_count", term(|t| t.flat_count), 2, |w| w.flat_count),
+                ("komi", Pair::new(0, s.half_komi), 1, |w| w.flat_count),
+                ("stones_left", term(|t| t.stones_left), 2, |w| w.stones_left),
+                ("caps_left", term(|t| t.caps_left), 2, |w| w.caps_left),
+                ("total_dist", term(|t| t.total_dist), 2, |w| w.total_dist),
+                ("smallest_dist", term(|t| t.smallest_dist), 2, |w| {
+                    w.smallest_dist
+                }),
+                (
+                    "side_to_move",
+                    Pair::new((color == WHITE) as i32, (color == BLACK) as i32),
+                    1,
+                    |w| w.side_to_move,
+                ),
+                ("reserves", term(|t| t.reserves), 2, |w| w.reserves),
+                ("captives", term(|t| t.captives), 2, |w| w.captives),
+                ("cap_stack", term(|t| t.cap_stack), 2, |w| w.cap_stack),
+            ]
+        };
+
+        writeln!(
+            f,
+            "terms for {} to move, phase {}/{PHASE_MAX}:",
+            color_name(color),
+            features.phase,
+        )?;
+        writeln!(
+            f,
+            "  {:<14}{:>14}{:>24}{:>27}",
+            "", "raw", "weight", "weighted",
+        )?;
+        writeln!(
+            f,
+            "  {:<14}{:>7}{:>7}{:>8}{:>8}{:>8}{:>9}{:>9}{:>9}",
+            "term", "white", "black", "opening", "endgame", "tapered", "white", "black", "score",
+        )?;
+
+        let mut total = 0.;
+        for (name, raw, scale, weight) in terms {
+            let (opening, endgame) = (weight(&params.opening), weight(&params.endgame));
+            let tapered = opening * features.phase + endgame * (PHASE_MAX - features.phase);
+            let scaled = |value: i32| value as f64 / PHASE_MAX as f64;
+
+            // Scores are from the point of view of the side to move
+            let score = |side| {
+                let sign = if side == color { 1 } else { -1 };
+                scaled(raw[side] * scale * sign * tapered)
+            };
+            let (white, black) = (score(WHITE), score(BLACK));
+            total += white + black;
+
+            writeln!(
+                f,
+                "  {name:<14}{:>7}{:>7}{opening:>8}{endgame:>8}{:>8.2}{white:>9.2}{black:>9.2}{:>9.2}",
+                raw.white,
+                raw.black,
+                scaled(tapered),
+                white + black,
+            )?;
+        }
+
+        // The evaluation divides the exact total once, rounding toward zero
+        write!(
+            f,
+            "  {:<70}{total:>9.2}\n  eval {} (the total rounded toward zero)",
+            "total",
+            features.weigh(&params),
+        )
+    }
+}
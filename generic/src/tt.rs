@@ -28,6 +28,14 @@ pub struct TtEntry {
     pub packed: Packed,
 }
 
+/// A cached static evaluation. The score is `None` in empty entries, since any signature,
+/// zero included, can belong to a real position.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EvalEntry {
+    pub sig: u64,
+    pub score: Option<Eval>,
+}
+
 // TODO: Cleanup
 #[inline]
 pub fn rate_entry(depth: u8, entry_gen: u32, curr_gen: u32) -> i32 {
@@ -0,0 +1,133 @@
+use std::{env::Args, fs, time::Duration};
+
+use cataklysm::{
+    ptn::{parse_collection, PtnGame},
+    util::{think, Limit},
+};
+use common::{game::*, pair::WHITE};
+
+const DEFAULT_DEPTH: u32 = 5;
+
+/// Score loss of the played move which marks it as an inaccuracy or a blunder.
+const INACCURACY: i32 = 20;
+const BLUNDER: i32 = 60;
+
+/// Best line found from a position, from the point of view of the side to move.
+struct Analysis {
+    eval: Eval,
+    best: String,
+    pv: String,
+}
+
+pub fn run(mut args: Args) {
+    let path = args.next().expect("missing PTN path");
+
+    let mut limit = Limit::Depth(DEFAULT_DEPTH);
+    let mut output = None;
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().expect("missing option value");
+        match arg.as_str() {
+            "--depth" => limit = Limit::Depth(value().parse().unwrap()),
+            "--movetime" => {
+                limit = Limit::Movetime(Duration::from_millis(value().parse().unwrap()))
+            }
+            "--output" => output = Some(value()),
+            _ => panic!(r#"unsupported option "{arg}""#),
+        }
+    }
+
+    let text = fs::read_to_string(&path).expect("could not read PTN file");
+    let annotated = parse_collection(&text)
+        .into_iter()
+        .map(|game| annotate(&game.expect("malformed PTN"), limit).to_string())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    match output {
+        Some(output) => fs::write(output, annotated).unwrap(),
+        None => print!("{annotated}"),
+    }
+}
+
+fn analyze(game: &mut dyn Game, limit: Limit) -> Analysis {
+    let (eval, _, best) = think(game, limit);
+    Analysis {
+        eval,
+        best: best.to_string(),
+        pv: game.pv().to_string(),
+    }
+}
+
+/// Evaluates a position in which the game may have ended.
+fn analyze_final(game: &mut dyn Game, limit: Limit) -> Eval {
+    let color = game.active_color();
+    match game.result() {
+        Some(GameResult::Road(winner) | GameResult::Flat(winner)) if winner == color => {
+            Eval::win(game.ply())
+        }
+        Some(GameResult::Road(_) | GameResult::Flat(_)) => Eval::loss(game.ply()),
+        Some(GameResult::Draw) => Eval::ZERO,
+        None => analyze(game, limit).eval,
+    }
+}
+
+fn annotate(ptn: &PtnGame, limit: Limit) -> PtnGame {
+    let opt = ptn.options().expect("unsupported game options");
+
+    let mut positions = Vec::new();
+    let mut colors = Vec::new();
+    let mut game = ptn
+        .replay(opt, |game, i| {
+            let analysis = analyze(game, limit);
+            eprintln!("{}: {}, pv {}", ptn.moves()[i], analysis.eval, analysis.pv);
+
+            colors.push(game.active_color());
+            positions.push(analysis);
+        })
+        .expect("could not replay game");
+    let final_eval = analyze_final(&mut *game, limit);
+
+    let mut annotated = ptn.clone();
+    let parse = game.parser();
+
+    for (i, analysis) in positions.iter().enumerate() {
+        let played_eval = -positions.get(i + 1).map_or(final_eval, |next| next.eval);
+        let white_eval = if colors[i] == WHITE {
+            played_eval
+        } else {
+            -played_eval
+        };
+
+        let comments = &mut annotated.comments[i + 1];
+        comments.push(white_eval.to_string());
+
+        let played = parse(&ptn.moves()[i].to_string())
+            .ok()
+            .map(|mv| mv.to_string());
+        if played.as_ref() == Some(&analysis.best) {
+            continue;
+        }
+
+        let best = analysis.eval;
+        let mark = if best >= Eval::DECISIVE && played_eval < Eval::DECISIVE {
+            Some("missed tinue")
+        } else if played_eval <= -Eval::DECISIVE && best > -Eval::DECISIVE {
+            Some("blunder")
+        } else if best.is_decisive() || played_eval.is_decisive() {
+            None
+        } else if best.raw() - played_eval.raw() >= BLUNDER {
+            Some("blunder")
+        } else if best.raw() - played_eval.raw() >= INACCURACY {
+            Some("inaccuracy")
+        } else {
+            None
+        };
+
+        if let Some(mark) = mark {
+            let best_white = if colors[i] == WHITE { best } else { -best };
+            comments.push(format!("{mark}, best {best_white}: {}", analysis.pv));
+        }
+    }
+
+    annotated
+}
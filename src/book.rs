@@ -0,0 +1,283 @@
+use std::{collections::HashMap, env::Args, fs};
+
+use cataklysm::ptn::{parse_collection, PtnGame};
+use common::{
+    game::*,
+    hash::Hash,
+    params::{SearchParams, SEARCH_PARAMS},
+};
+
+use cataklysm::util::Rng;
+
+const MAGIC: &[u8; 6] = b"CATKBK";
+const VERSION: u8 = 1;
+const HEADER_LEN: usize = 16;
+
+const DEFAULT_PLIES: usize = 16;
+const DEFAULT_MIN_GAMES: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BookEntry {
+    pub hash: u64,
+    /// Number of games in which the move was played from the position.
+    pub weight: u32,
+    pub mv: String,
+}
+
+/// Moves played from positions keyed by their Zobrist hash.
+///
+/// The file format is a 16-byte header (magic, version, board size, entry count) followed by
+/// entries sorted by hash, each a little-endian `u64` hash, `u32` weight, and a move in PTN
+/// prefixed by its length as a byte.
+#[derive(Debug)]
+pub struct Book {
+    size: usize,
+    entries: Vec<BookEntry>,
+}
+
+impl Book {
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        let (header, mut body) = data.split_at_checked(HEADER_LEN)?;
+
+        if &header[..6] != MAGIC || header[6] != VERSION {
+            return None;
+        }
+
+        let size = header[7] as usize;
+        let len = u64::from_le_bytes(header[8..].try_into().unwrap()) as usize;
+
+        let mut entries = Vec::with_capacity(len.min(body.len() / 13));
+        for _ in 0..len {
+            let (fixed, rest) = body.split_at_checked(13)?;
+            let (mv, rest) = rest.split_at_checked(fixed[12] as usize)?;
+            body = rest;
+
+            entries.push(BookEntry {
+                hash: u64::from_le_bytes(fixed[..8].try_into().unwrap()),
+                weight: u32::from_le_bytes(fixed[8..12].try_into().unwrap()),
+                mv: String::from_utf8(mv.to_vec()).ok()?,
+            });
+        }
+
+        (body.is_empty() && entries.windows(2).all(|w| w[0].hash <= w[1].hash))
+            .then_some(Self { size, entries })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+
+        data.extend_from_slice(MAGIC);
+        data.push(VERSION);
+        data.push(self.size as u8);
+        data.extend_from_slice(&(self.entries.len() as u64).to_le_bytes());
+
+        for entry in &self.entries {
+            data.extend_from_slice(&entry.hash.to_le_bytes());
+            data.extend_from_slice(&entry.weight.to_le_bytes());
+            data.push(entry.mv.len() as u8);
+            data.extend_from_slice(entry.mv.as_bytes());
+        }
+
+        data
+    }
+
+    /// Builds a book from the first `plies` of the games of the given size, keeping the moves
+    /// played in at least `min_games` of them. Also returns the numbers of games used and skipped.
+    pub fn from_games<'a>(
+        size: usize,
+        games: impl IntoIterator<Item = &'a PtnGame>,
+        plies: usize,
+        min_games: u32,
+    ) -> (Self, usize, usize) {
+        let mut counts = HashMap::<(u64, String), u32>::new();
+        let (mut used, mut skipped) = (0, 0);
+
+        for game in games {
+            match add_game(game, plies).filter(|_| game.size() == Some(size)) {
+                Some(moves) => {
+                    for key in moves {
+                        *counts.entry(key).or_default() += 1;
+                    }
+                    used += 1;
+                }
+                None => skipped += 1,
+            }
+        }
+
+        let mut entries = counts
+            .into_iter()
+            .filter(|&(_, weight)| weight >= min_games)
+            .map(|((hash, mv), weight)| BookEntry { hash, weight, mv })
+            .collect::<Vec<_>>();
+        entries.sort_unstable_by(|a, b| (a.hash, &a.mv).cmp(&(b.hash, &b.mv)));
+
+        (Self { size, entries }, used, skipped)
+    }
+
+    pub fn moves(&self, hash: Hash) -> &[BookEntry] {
+        let start = self.entries.partition_point(|e| e.hash < hash.raw());
+        let end = self.entries.partition_point(|e| e.hash <= hash.raw());
+        &self.entries[start..end]
+    }
+
+    /// Picks a move for the position at random, in proportion to the weights.
+    pub fn choose(&self, hash: Hash, rng: &mut Rng) -> Option<&str> {
+        let moves = self.moves(hash);
+        let total = moves.iter().map(|e| e.weight as u64).sum::<u64>();
+        if total == 0 {
+            return None;
+        }
+
+        let mut pick = rng.next_u64() % total;
+        moves.iter().find_map(|e| {
+            if pick < e.weight as u64 {
+                Some(e.mv.as_str())
+            } else {
+                pick -= e.weight as u64;
+                None
+            }
+        })
+    }
+}
+
+pub fn run(mut args: Args) {
+    match args.next().as_deref() {
+        Some("build") => build(args),
+        _ => panic!("unsupported book command"),
+    }
+}
+
+fn build(mut args: Args) {
+    let size: usize = args.next().expect("missing size").parse().unwrap();
+    let path = args.next().expect("missing output path");
+
+    let mut plies = DEFAULT_PLIES;
+    let mut min_games = DEFAULT_MIN_GAMES;
+    let mut inputs = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--plies" => plies = args.next().unwrap().parse().unwrap(),
+            "--min-games" => min_games = args.next().unwrap().parse().unwrap(),
+            _ => inputs.push(arg),
+        }
+    }
+
+    let mut games = Vec::new();
+    let mut malformed = 0;
+    for input in &inputs {
+        let text = fs::read_to_string(input).expect("could not read PTN file");
+        for game in parse_collection(&text) {
+            match game {
+                Ok(game) => games.push(game),
+                Err(_) => malformed += 1,
+            }
+        }
+    }
+
+    let (book, used, skipped) = Book::from_games(size, &games, plies, min_games);
+    let entries = &book.entries;
+
+    let positions = entries
+        .windows(2)
+        .filter(|w| w[0].hash != w[1].hash)
+        .count()
+        + !entries.is_empty() as usize;
+    println!(
+        "{used} games used, {} skipped, {} moves from {positions} positions",
+        skipped + malformed,
+        entries.len(),
+    );
+
+    fs::write(path, book.to_bytes()).unwrap();
+}
+
+/// Returns the hashes of the positions in the first plies of a game with the moves played from
+/// them, or `None` if the game could not be replayed.
+fn add_game(game: &PtnGame, plies: usize) -> Option<Vec<(u64, String)>> {
+    let mut game = game.clone();
+    game.truncate(plies);
+
+    let opt = Options {
+        params: SearchParams {
+            tt_size: 1,
+            ..SEARCH_PARAMS
+        },
+        ..game.options()?
+    };
+
+    let mut seen = Vec::new();
+    game.replay(opt, |state, i| {
+        if let Ok(action) = state.parser()(&game.moves()[i].to_string()) {
+            seen.push((state.hash().raw(), action.to_string()));
+        }
+    })
+    .ok()?;
+
+    Some(seen)
+}
+
+#[cfg(test)]
+mod tests {
+    use cataklysm::new_game;
+
+    use super::*;
+
+    const GAMES: &str = "[Size \"5\"]\n\n1. a1 e5 2. c3 d3\n\n\
+        [Size \"5\"]\n\n1. a1 e5 2. c3 c4\n\n\
+        [Size \"5\"]\n\n1. a1 e1 2. b2 c3\n\n\
+        [Size \"6\"]\n\n1. a1 f6 2. c3 d4\n";
+
+    fn build(plies: usize, min_games: u32) -> (Book, usize, usize) {
+        let games = parse_collection(GAMES)
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        Book::from_games(5, &games, plies, min_games)
+    }
+
+    #[test]
+    fn from_games() {
+        let (book, used, skipped) = build(3, 1);
+        assert_eq!((used, skipped), (3, 1));
+
+        let mut game = new_game(5, Options::default(5).unwrap()).unwrap();
+        let mut moves = |mv: Option<&str>| {
+            if let Some(mv) = mv {
+                let action = game.parser()(mv).unwrap();
+                game.play(action).unwrap();
+            }
+            book.moves(game.hash())
+                .iter()
+                .map(|e| (e.mv.as_str(), e.weight))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(moves(None), [("a1", 3)]);
+        assert_eq!(moves(Some("a1")), [("e1", 1), ("e5", 2)]);
+        assert_eq!(moves(Some("e5")), [("c3", 2)]);
+        // Only the first three plies are kept
+        assert_eq!(moves(Some("c3")), []);
+
+        let (book, _, _) = build(3, 2);
+        assert_eq!(book.entries.len(), 3);
+        assert!(book.entries.iter().all(|e| e.weight >= 2));
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let (book, _, _) = build(4, 1);
+        let data = book.to_bytes();
+
+        let read = Book::from_bytes(&data).unwrap();
+        assert_eq!(read.size, book.size);
+        assert_eq!(read.entries, book.entries);
+        assert_eq!(read.to_bytes(), data);
+
+        assert!(Book::from_bytes(&data[..data.len() - 1]).is_none());
+        assert!(Book::from_bytes(&[data.as_slice(), &[0]].concat()).is_none());
+    }
+}
@@ -0,0 +1,235 @@
+use std::{
+    env::Args,
+    fs::File,
+    io::{self, BufWriter, Write},
+    sync::{
+        atomic::{AtomicU64, Ordering::Relaxed},
+        mpsc::channel,
+    },
+    thread::scope,
+    time::Instant,
+};
+
+use cataklysm::new_game;
+use common::{
+    game::*,
+    pair::{BLACK, WHITE},
+    params::{SearchParams, SEARCH_PARAMS},
+};
+
+use cataklysm::util::{random_opening, think, Limit, Rng};
+
+const DEFAULT_THREADS: usize = 1;
+const DEFAULT_LIMIT: Limit = Limit::Nodes(20_000);
+const DEFAULT_SEED: u64 = 1;
+
+/// Header of a dataset, followed by the version and the board size as bytes.
+pub const MAGIC: &[u8; 6] = b"CATKDG";
+const VERSION: u8 = 1;
+const HEADER_LEN: usize = 8;
+
+const MAX_PLIES: u32 = 300;
+const RANDOM_PLIES: usize = 6;
+const TT_SIZE: usize = 1 << 16;
+
+fn parse_limit(s: &str) -> Option<Limit> {
+    let (kind, value) = s.split_once(':')?;
+    match kind {
+        "nodes" => value.parse().ok().map(Limit::Nodes),
+        "depth" => value.parse().ok().map(Limit::Depth),
+        _ => None,
+    }
+}
+
+/// A searched position and the result of its game.
+pub struct Record {
+    /// Position as encoded by [`Game::encode_position`].
+    pub position: Vec<u8>,
+    /// Search score from the point of view of white.
+    pub score: i32,
+    pub best: String,
+    pub result: GameResult,
+}
+
+impl Record {
+    /// Writes the length of the position as a little-endian `u16` followed by the position, the
+    /// score as a little-endian `i32`, the length of the move as a byte followed by the move in
+    /// PTN, and the result as a byte.
+    fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        out.write_all(&(self.position.len() as u16).to_le_bytes())?;
+        out.write_all(&self.position)?;
+        out.write_all(&self.score.to_le_bytes())?;
+        out.write_all(&[self.best.len() as u8])?;
+        out.write_all(self.best.as_bytes())?;
+        out.write_all(&[match self.result {
+            GameResult::Draw => 0,
+            GameResult::Road(WHITE) => 1,
+            GameResult::Road(BLACK) => 2,
+            GameResult::Flat(WHITE) => 3,
+            GameResult::Flat(BLACK) => 4,
+        }])
+    }
+
+    fn read(data: &mut &[u8]) -> Option<Self> {
+        let mut bytes = |len| {
+            let (bytes, rest) = data.split_at_checked(len)?;
+            *data = rest;
+            Some(bytes)
+        };
+
+        let len = u16::from_le_bytes(bytes(2)?.try_into().unwrap());
+        let position = bytes(len as usize)?.to_vec();
+        let score = i32::from_le_bytes(bytes(4)?.try_into().unwrap());
+        let len = bytes(1)?[0];
+        let best = String::from_utf8(bytes(len as usize)?.to_vec()).ok()?;
+        let result = match bytes(1)?[0] {
+            0 => GameResult::Draw,
+            1 => GameResult::Road(WHITE),
+            2 => GameResult::Road(BLACK),
+            3 => GameResult::Flat(WHITE),
+            4 => GameResult::Flat(BLACK),
+            _ => return None,
+        };
+
+        Some(Self {
+            position,
+            score,
+            best,
+            result,
+        })
+    }
+}
+
+/// Reads a dataset written by `datagen`, returning the board size and the records.
+pub fn read(mut data: &[u8]) -> Option<(usize, Vec<Record>)> {
+    let (header, rest) = data.split_at_checked(HEADER_LEN)?;
+    if &header[..6] != MAGIC || header[6] != VERSION {
+        return None;
+    }
+    data = rest;
+
+    let mut records = Vec::new();
+    while !data.is_empty() {
+        records.push(Record::read(&mut data)?);
+    }
+
+    Some((header[7] as usize, records))
+}
+
+/// Plays games against itself and writes a dataset of the searched positions, which `tune` reads
+/// directly. After an 8-byte header (magic, version, board size) come the records, each written
+/// by [`Record::write`].
+pub fn run(mut args: Args) {
+    let size: usize = args.next().expect("missing size").parse().unwrap();
+    let games: u64 = args.next().expect("missing game count").parse().unwrap();
+    let path = args.next().expect("missing output path");
+    let threads = args.next().map_or(DEFAULT_THREADS, |n| n.parse().unwrap());
+    let limit = args
+        .next()
+        .map_or(DEFAULT_LIMIT, |l| parse_limit(&l).expect("invalid limit"));
+    let seed = args.next().map_or(DEFAULT_SEED, |n| n.parse().unwrap());
+    let quiet = match args.next().as_deref() {
+        None | Some("all") => false,
+        Some("quiet") => true,
+        Some(_) => panic!("invalid filter"),
+    };
+
+    let mut out = BufWriter::new(File::create(&path).expect("could not create output file"));
+    out.write_all(MAGIC).unwrap();
+    out.write_all(&[VERSION, size as u8]).unwrap();
+    let time = Instant::now();
+
+    let next_game = AtomicU64::new(0);
+    let (tx, rx) = channel();
+
+    scope(|scope| {
+        for _ in 0..threads {
+            let tx = tx.clone();
+            let next_game = &next_game;
+
+            scope.spawn(move || loop {
+                let index = next_game.fetch_add(1, Relaxed);
+                if index >= games {
+                    break;
+                }
+
+                let mut rng = Rng(seed.wrapping_add(index));
+                let (records, result) = play_game(size, limit, quiet, &mut rng);
+                tx.send((records, result)).unwrap();
+            });
+        }
+        drop(tx);
+
+        let mut positions = 0;
+        for (done, (records, result)) in rx.into_iter().enumerate() {
+            for record in &records {
+                record.write(&mut out).unwrap();
+            }
+            positions += records.len();
+
+            println!(
+                "game {}/{games}: {result}, {positions} positions in {:.1}s",
+                done + 1,
+                time.elapsed().as_secs_f64(),
+            );
+        }
+    });
+
+    out.flush().unwrap();
+}
+
+fn play_game(size: usize, limit: Limit, quiet: bool, rng: &mut Rng) -> (Vec<Record>, GameResult) {
+    let mut game = new_game(
+        size,
+        Options {
+            params: SearchParams {
+                tt_size: TT_SIZE,
+                ..SEARCH_PARAMS
+            },
+            ..Options::default(size).unwrap()
+        },
+    )
+    .unwrap();
+
+    for mv in random_opening(size, RANDOM_PLIES, rng) {
+        let mv = game.parser()(&mv).unwrap();
+        game.play(mv).unwrap();
+    }
+
+    let mut positions = Vec::new();
+    let result = loop {
+        if let Some(result) = game.result() {
+            break result;
+        }
+        if game.ply() >= MAX_PLIES {
+            break GameResult::Draw;
+        }
+
+        let (eval, _, best) = think(&mut *game, limit);
+        let best = best.to_string();
+        let parse = game.parser();
+
+        if !quiet || !(eval.is_decisive() || game.is_tactical(parse(&best).unwrap())) {
+            let score = if game.active_color() == WHITE {
+                eval.raw()
+            } else {
+                -eval.raw()
+            };
+
+            positions.push((game.encode_position(), score, best.clone()));
+        }
+
+        game.play(parse(&best).unwrap()).unwrap();
+    };
+
+    let records = positions
+        .into_iter()
+        .map(|(position, score, best)| Record {
+            position,
+            score,
+            best,
+            result,
+        })
+        .collect();
+    (records, result)
+}
@@ -1,3 +1,8 @@
+pub mod playtak;
+pub mod ptn;
+pub mod testsuite;
+pub mod util;
+
 use common::game::*;
 pub use common::*;
 
@@ -19,6 +24,25 @@ pub fn new_game(size: usize, opt: Options) -> Result<Box<dyn Game>, NewGameError
     })
 }
 
+/// Returns the default evaluation parameters for the board size.
+pub fn eval_params(size: usize) -> Option<params::EvalParams> {
+    Some(match size {
+        #[cfg(feature = "3")]
+        3 => size3::SIZE_EVAL_PARAMS,
+        #[cfg(feature = "4")]
+        4 => size4::SIZE_EVAL_PARAMS,
+        #[cfg(feature = "5")]
+        5 => size5::SIZE_EVAL_PARAMS,
+        #[cfg(feature = "6")]
+        6 => size6::SIZE_EVAL_PARAMS,
+        #[cfg(feature = "7")]
+        7 => size7::SIZE_EVAL_PARAMS,
+        #[cfg(feature = "8")]
+        8 => size8::SIZE_EVAL_PARAMS,
+        _ => return None,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -36,4 +60,288 @@ mod tests {
             .unwrap();
         assert_eq!(game.perft(depth, PerftMode::Batch), expected);
     }
+
+    #[rstest]
+    #[case("x5/x5/1,1,1,1,x/x5/2,2,2,x2 1 5", Proof::Win)]
+    #[case("1,1,1,1,x/x5/1,1,1,1,x/x5/2,2,2,x2 2 6", Proof::Loss)]
+    fn prove_short(#[case] tps: &str, #[case] expected: Proof) {
+        let mut game = new_game(5, Options::default(5).unwrap()).unwrap();
+        game.set_position(tps).unwrap();
+        assert_eq!(game.prove(1_000_000), expected);
+    }
+
+    #[rstest]
+    #[case(2)]
+    #[case(3)]
+    fn tablebase_agrees_with_proof(#[case] stones: u32) {
+        let opt = || Options {
+            start_stones: pair::Pair::both(stones),
+            ..Options::default(3).unwrap()
+        };
+
+        let mut game = new_game(3, opt()).unwrap();
+        let tablebase = game.solve(1_000_000).unwrap();
+        let proof = game.prove(1_000_000);
+
+        let mut game = new_game(3, opt()).unwrap();
+        game.set_tablebase(&tablebase).unwrap();
+        let (eval, _) = game.search(1).unwrap();
+
+        match proof {
+            Proof::Win => assert!(eval >= Eval::DECISIVE),
+            Proof::Loss => assert!(eval <= -Eval::DECISIVE),
+            Proof::Draw => assert_eq!(eval, Eval::ZERO),
+            Proof::Unknown => panic!("proof search ran out of nodes"),
+        }
+    }
+
+    #[test]
+    fn tablebase_rejects_other_options() {
+        let opt = |stones, half_komi| Options {
+            start_stones: pair::Pair::both(stones),
+            half_komi,
+            ..Options::default(3).unwrap()
+        };
+        let tablebase = new_game(3, opt(2, 0)).unwrap().solve(1_000_000).unwrap();
+
+        for (stones, half_komi) in [(3, 0), (2, 2)] {
+            let mut game = new_game(3, opt(stones, half_komi)).unwrap();
+            let result = game.set_tablebase(&tablebase);
+            assert_eq!(result, Err(TablebaseError::Mismatch));
+        }
+        let mut game = new_game(3, opt(2, 0)).unwrap();
+        assert!(game.set_tablebase(&tablebase).is_ok());
+        assert_eq!(
+            game.set_tablebase(&tablebase[1..]),
+            Err(TablebaseError::Malformed)
+        );
+    }
+
+    #[rstest]
+    #[case(2, 0, 3)]
+    #[case(2, 2, 3)]
+    #[case(2, 4, 4)]
+    fn endgame_matches_tablebase(#[case] stones: u32, #[case] half_komi: i32, #[case] depth: u32) {
+        let opt = || Options {
+            start_stones: pair::Pair::both(stones),
+            half_komi,
+            ..Options::default(3).unwrap()
+        };
+
+        let mut game = new_game(3, opt()).unwrap();
+        let tablebase = game.solve(1_000_000).unwrap();
+        game.set_tablebase(&tablebase).unwrap();
+        let (expected, _) = game.search(1).unwrap();
+
+        let mut game = new_game(3, opt()).unwrap();
+        let (eval, _) = game.search(depth).unwrap();
+
+        assert_eq!(eval, expected);
+    }
+
+    #[rstest]
+    #[case(
+        "x2,2,x2/1,2,1,x2/x,121,2212C,x,1/2,1,x3/x2,1,x2 2 11",
+        "3c3<",
+        "3c3<12"
+    )]
+    #[case("1,x4/x,2,1221,x2/2,x,1121C,1,x/x,2,x3/x2,1,x,2 2 10", "e3", "Cb3")]
+    fn stack_terms_change_move(
+        #[case] tps: &str,
+        #[case] with_stacks: &str,
+        #[case] without_stacks: &str,
+    ) {
+        let best = |eval| {
+            let mut game = new_game(
+                5,
+                Options {
+                    eval: Some(eval),
+                    ..Options::default(5).unwrap()
+                },
+            )
+            .unwrap();
+            game.set_position(tps).unwrap();
+            game.search(4).unwrap().1.to_string()
+        };
+
+        let with = eval_params(5).unwrap();
+        let mut without = with;
+        for weights in [&mut without.opening, &mut without.endgame] {
+            weights.reserves = 0;
+            weights.captives = 0;
+            weights.cap_stack = 0;
+        }
+
+        assert_eq!(best(with), with_stacks);
+        assert_eq!(best(without), without_stacks,);
+    }
+
+    #[rstest]
+    #[case("x2,2,x2/1,2,1,x2/x,121,2212C,x,1/2,1,x3/x2,1,x2 2 11", 0)]
+    #[case("1,x4/x,2,1221,x2/2,x,1121C,1,x/x,2,x3/x2,1,x,2 2 10", 3)]
+    #[case("x5/x5/x5/x5/1,2,x3 1 3", -2)]
+    fn eval_trace_adds_up(#[case] tps: &str, #[case] half_komi: i32) {
+        // Different endgame weights make the tapered weights fractional
+        let mut eval = eval_params(5).unwrap();
+        eval.endgame.flat_count += 3;
+        eval.endgame.reserves += 1;
+
+        let mut game = new_game(
+            5,
+            Options {
+                half_komi,
+                eval: Some(eval),
+                ..Options::default(5).unwrap()
+            },
+        )
+        .unwrap();
+        game.set_position(tps).unwrap();
+
+        let trace = game.eval_trace().to_string();
+        let number = |prefix| {
+            let line = trace
+                .lines()
+                .find_map(|line| line.trim().strip_prefix(prefix));
+            line.unwrap().split_whitespace().next().unwrap().to_string()
+        };
+
+        let rows = trace
+            .lines()
+            .skip_while(|line| !line.trim().starts_with("term "))
+            .skip(1)
+            .take_while(|line| !line.trim().starts_with("total "));
+        let sum = rows
+            .map(|row| {
+                row.split_whitespace()
+                    .last()
+                    .unwrap()
+                    .parse::<f64>()
+                    .unwrap()
+            })
+            .sum::<f64>();
+        let total = number("total ").parse::<f64>().unwrap();
+        let weighed = game.eval_features().weigh(&eval);
+
+        assert!((sum - total).abs() < 0.1, "{trace}");
+        assert_eq!(total.trunc() as i32, weighed);
+        assert_eq!(number("eval ").parse::<i32>().unwrap(), weighed);
+    }
+
+    #[rstest]
+    #[case(
+        "[Size \"5\"]\n[Komi \"1.5\"]\n[Result \"R-0\"]\n\n\
+         1. a1 e5 {opposite corners} 2. c3 c4 3. d3 b4 {threat} 4. b3 R-0",
+        "x4,1/x,2,2,x2/x,1,1,1,x/x5/2,x4 2 4"
+    )]
+    #[case(
+        "[Size \"6\"]\n[TPS \"2,x5/x6/x6/x4,1,x/x6/x5,1 2 2\"]\n\n2. -- c4 {centre} 3. d3 d4 0-1",
+        "2,x5/x6/x2,2,2,x2/x3,1,1,x/x6/x5,1 1 4"
+    )]
+    fn ptn_round_trip(#[case] text: &str, #[case] end: &str) {
+        let game = text.parse::<ptn::PtnGame>().unwrap();
+        let replayed = game.replay(game.options().unwrap(), |_, _| ()).unwrap();
+        assert_eq!(replayed.tps().to_string(), end);
+
+        let written = game.to_string();
+        assert_eq!(written.parse::<ptn::PtnGame>().unwrap(), game);
+
+        let collection = format!("{written}\n{text}");
+        let games = ptn::parse_collection(&collection);
+        assert_eq!(games, [Ok(game.clone()), Ok(game)]);
+    }
+
+    #[test]
+    fn ptn_rejects_invalid_input() {
+        let mut game = ptn::PtnGame::new(5, 0);
+        assert!(game.push("a1", Vec::new()).is_ok());
+        assert_eq!(
+            game.push("z9+", Vec::new()),
+            Err(ptn::PtnError::IllegalMove(1))
+        );
+        game.set_other_win(pair::BLACK);
+        assert_eq!(
+            game.to_string(),
+            "[Size \"5\"]\n[Result \"0-1\"]\n\n1. a1 0-1\n"
+        );
+
+        assert!(game.options().is_some());
+        game.set_tag("Komi", "1.25");
+        assert!(game.options().is_none());
+    }
+
+    #[rstest]
+    #[case("x5/x5/x5/x5/x5 1 1", "Sa1", PlayMoveError::Opening)]
+    #[case("x5/x5/x5/x5/1C,2,x3 1 2", "a1", PlayMoveError::Occupied)]
+    #[case("x5/x5/x5/x5/1C,2,x3 1 2", "Cc3", PlayMoveError::NoCaps)]
+    #[case("x5/x5/x5/x5/1C,2,x3 1 2", "b1>", PlayMoveError::NotControlled)]
+    #[case("x5/x5/x5/x5/1C,2,x3 1 2", "2a1>", PlayMoveError::CarryLimit)]
+    #[case("x5/x5/x5/x5/1C,2,x3 1 2", "a1<", PlayMoveError::OffBoard)]
+    #[case("x5/x5/x5/x5/1,2S,x3 1 2", "a1>", PlayMoveError::Blocked)]
+    fn illegal_move_reason(#[case] tps: &str, #[case] mv: &str, #[case] error: PlayMoveError) {
+        let mut game = new_game(5, Options::default(5).unwrap()).unwrap();
+        game.set_position(tps).unwrap();
+        let action = game.parser()(mv).unwrap();
+        assert_eq!(game.play(action), Err(error));
+    }
+
+    #[test]
+    fn tinue_suite() {
+        let positions = testsuite::parse_suite(include_str!("../suites/tinue.txt")).unwrap();
+        let mut failed = Vec::new();
+        testsuite::run_suite(&positions, util::Limit::Depth(3), |position, outcome| {
+            if !outcome.passed {
+                failed.push((position.id.clone(), outcome.answer.clone()));
+            }
+        })
+        .unwrap();
+
+        assert_eq!(failed, []);
+    }
+
+    #[rstest]
+    #[case("P A1", "a1")]
+    #[case("P C3 W", "Sc3")]
+    #[case("P E5 C", "Ce5")]
+    #[case("M A1 A3 1 2", "3a1+12")]
+    #[case("M E2 C2 1 1", "2e2<11")]
+    #[case("M D4 D3 1", "1d4-")]
+    #[case("M A1 E1 1 1 1 1", "4a1>1111")]
+    fn playtak_notation(#[case] server: &str, #[case] ptn: &str) {
+        let mut game = new_game(5, Options::default(5).unwrap()).unwrap();
+        let parse = game.parser();
+
+        let converted = game.playtak_parser()(server).unwrap();
+        assert_eq!(converted.to_string(), parse(ptn).unwrap().to_string());
+        assert_eq!(game.playtak_move(parse(ptn).unwrap()).to_string(), server);
+    }
+
+    #[test]
+    fn playtak_replay() {
+        let session = include_str!("../testdata/playtak_guest.txt");
+        let lines = |prefix| {
+            session
+                .lines()
+                .filter_map(|line| line.strip_prefix(prefix))
+                .map(|line| format!("{line}\n"))
+                .collect::<String>()
+        };
+
+        let settings = playtak::Settings {
+            user: "Guest".into(),
+            seek: Some(playtak::Seek {
+                size: 5,
+                time: 60,
+                increment: 2,
+                color: 'A',
+                half_komi: 0,
+            }),
+            games: 1,
+            depth: Some(3),
+            ..Default::default()
+        };
+
+        let mut sent = Vec::new();
+        playtak::run(lines("< ").as_bytes(), &mut sent, &settings, |_| ()).unwrap();
+        assert_eq!(String::from_utf8(sent).unwrap(), lines("> "));
+    }
 }
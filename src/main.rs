@@ -1,16 +1,68 @@
+mod analyze;
+mod book;
+mod datagen;
+mod matchup;
+mod play;
+mod spsa;
 mod tei;
+mod tournament;
+mod tune;
 
 use std::{
     env::{args, Args},
-    io::stdin,
-    time::Instant,
+    fs,
+    io::{stdin, BufReader},
+    net::TcpStream,
+    process,
+    time::{Duration, Instant},
 };
 
-use cataklysm::new_game;
-use common::game::*;
+use cataklysm::{
+    new_game, playtak,
+    ptn::PtnGame,
+    testsuite::{parse_suite, run_suite},
+    util,
+};
+use common::{
+    game::*,
+    params::{SearchParams, SEARCH_PARAMS},
+};
 
 use tokio::runtime::Builder;
 
+const DEFAULT_PROOF_NODES: u64 = 10_000_000;
+const DEFAULT_SOLVE_POSITIONS: usize = 50_000_000;
+const DEFAULT_SUITE_DEPTH: u32 = 5;
+const DEFAULT_EVALBENCH_RUNS: u32 = 5;
+const DEFAULT_PLAYTAK_SERVER: &str = "playtak.com:10000";
+/// Idle time after which a keepalive is sent to the PlayTak server.
+const PLAYTAK_KEEPALIVE: Duration = Duration::from_secs(30);
+
+/// Positions searched by bench, with the search depth for each.
+const BENCH_POSITIONS: &[(&str, u32)] = &[
+    ("x3/x,1,12/1,x,12 1 5", 10),
+    ("x,1,2/x,1212S,1/x,1,x 2 6", 10),
+    ("x,2,x,2/x4/x3,1/1,1,12,x 1 5", 8),
+    ("x,1,x2/1,1,x,212/x,12,2,x/x,1,x,2 1 8", 8),
+    ("x4,2/x4,2/x4,2/x5/1,1,1,1,2 1 5", 7),
+    ("1,12C,21C,2,2/2,x,2,x2/2,1,1,x2/x,1,x3/x,1,x3 1 9", 7),
+    ("x5,2/x5,2/x5,2/x5,2/x6/1,1,1,1,x2 1 5", 6),
+    (
+        "1,12C,2,2,2,2/2,x,1C,x3/2,1,1,x3/x,1,x4/x,1,x4/x,1,x4 1 9",
+        6,
+    ),
+    ("x6,2/x7/x7/x7/2C,x6/1,x3,2C,x,1/1,1,x4,2 1 5", 5),
+    (
+        "1,2,2,x3,2/2C,121C,2,2,x3/x,1,x5/x7/x,1,x5/x,1,x5/x,1,x5 1 9",
+        5,
+    ),
+    ("x7,2/x8/x8/x8/x8/2C,x7/1,x4,2C,x,1/1,1,x5,2 1 5", 5),
+    (
+        "1,2,2,2,x3,2/1,2,2,x5/2C,1,x6/x,1,x6/x,2C,x6/x,1,1,x5/x,1,x6/x,1,x6 1 9",
+        5,
+    ),
+];
+
 fn main() {
     let mut args = args();
 
@@ -21,19 +73,40 @@ fn main() {
     match mode.as_str() {
         "perft" => perft(args),
         "search" => search(args),
+        "eval" => eval(args),
+        "show" => show(args),
+        "prove" => prove(args),
+        "solve" => solve(args),
         "showmatch" => showmatch(args),
         "hashtest" => hashtest(args),
-        "tei" => Builder::new_current_thread()
-            .enable_all()
-            .build()
-            .unwrap()
-            .block_on(tei::run()),
+        "bench" => bench(args),
+        "evalbench" => evalbench(args),
+        "tune" => tune::run(args),
+        "spsa" => spsa::run(args),
+        "match" => matchup::run(args),
+        "tournament" => tournament::run(args),
+        "datagen" => datagen::run(args),
+        "book" => book::run(args),
+        "analyze" => analyze::run(args),
+        "testsuite" => testsuite(args),
+        "playtak" => playtak(args),
+        "play" => play::run(args),
+        "tei" => {
+            let size = args
+                .next()
+                .map_or(tei::DEFAULT_SIZE, |s| s.parse().unwrap());
+            Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap()
+                .block_on(tei::run(size))
+        }
         _ => help(),
     }
 }
 
-fn perft(args: Args) {
-    let mut game = make_game(args);
+fn perft(mut args: Args) {
+    let mut game = make_game(&mut args);
     for d in 1.. {
         for mode in [PerftMode::Batch, PerftMode::Naive] {
             let time = Instant::now();
@@ -53,8 +126,12 @@ fn perft(args: Args) {
     }
 }
 
-fn search(args: Args) {
-    let mut game = make_game(args);
+fn search(mut args: Args) {
+    let mut game = make_game(&mut args);
+    if let Some(path) = args.next() {
+        game.set_tablebase(&fs::read(path).unwrap()).unwrap();
+    }
+
     for d in 1..30 {
         let time = Instant::now();
         game.clear_nodes();
@@ -75,9 +152,79 @@ fn search(args: Args) {
     }
 }
 
-fn showmatch(args: Args) {
-    let mut game = make_game(args);
-    loop {
+fn eval(mut args: Args) {
+    let game = make_game(&mut args);
+    println!("{}", game.eval_trace());
+}
+
+fn show(mut args: Args) {
+    let game = make_game(&mut args);
+    println!("{}", game.board());
+}
+
+fn prove(mut args: Args) {
+    let mut game = make_game(&mut args);
+    let max_nodes = args
+        .next()
+        .map_or(DEFAULT_PROOF_NODES, |n| n.parse().unwrap());
+
+    let time = Instant::now();
+    game.clear_nodes();
+    let proof = game.prove(max_nodes);
+    let secs = time.elapsed().as_secs_f64();
+    let nodes = game.nodes();
+
+    println!(
+        "{proof}, {nodes} nodes in {secs:.2}s ({:.2} Mnps)",
+        nodes as f64 / secs / 1_000_000.,
+    );
+}
+
+fn solve(mut args: Args) {
+    let mut game = make_game(&mut args);
+    let path = args.next().unwrap();
+    let max_positions = args
+        .next()
+        .map_or(DEFAULT_SOLVE_POSITIONS, |n| n.parse().unwrap());
+
+    let time = Instant::now();
+    let data = match game.solve(max_positions) {
+        Ok(data) => data,
+        Err(TablebaseError::TooManyPositions) => {
+            println!(
+                "position cannot be solved within {max_positions} positions, \
+                 only reduced reserves or late positions fit in memory"
+            );
+            return;
+        }
+        Err(e) => {
+            println!("position cannot be solved: {e:?}");
+            return;
+        }
+    };
+    println!(
+        "solved in {:.2}s, writing {} bytes",
+        time.elapsed().as_secs_f64(),
+        data.len(),
+    );
+
+    fs::write(path, data).unwrap();
+}
+
+fn showmatch(mut args: Args) {
+    let mut game = make_game(&mut args);
+
+    let start = game.tps().to_string();
+    let mut ptn = PtnGame::new(size_of_tps(&start), 0);
+    ptn.set_tag("Player1", "cataklysm");
+    ptn.set_tag("Player2", "cataklysm");
+    ptn.set_tag("TPS", start);
+
+    let result = loop {
+        if let Some(result) = game.result() {
+            break result;
+        }
+
         let mut action;
         let mut d = 1;
         loop {
@@ -95,18 +242,19 @@ fn showmatch(args: Args) {
         }
 
         println!("{action}");
+        ptn.push(&action, Vec::new()).unwrap();
         game.play(action).unwrap();
+    };
 
-        // FIXME: Aborts game too early
-        if d == 1 {
-            println!("game finished");
-            break;
-        }
+    println!("game finished: {result}");
+    ptn.set_result(result);
+    if let Some(path) = args.next() {
+        fs::write(path, ptn.to_string()).unwrap();
     }
 }
 
-fn hashtest(args: Args) {
-    let mut game = make_game(args);
+fn hashtest(mut args: Args) {
+    let mut game = make_game(&mut args);
     let mut stdin = stdin().lines().map(|l| l.unwrap());
     loop {
         println!("{:?}", game.hash());
@@ -122,18 +270,221 @@ fn hashtest(args: Args) {
     }
 }
 
+/// Searches the built-in positions to a fixed depth. The total node count is a signature of the
+/// search, which changes only with its behavior, and can be checked against an expected value.
+fn bench(mut args: Args) {
+    let expected = args.next().map(|n| n.parse::<u64>().unwrap());
+
+    let mut total_nodes = 0;
+    let mut total_secs = 0.;
+    for &(tps, depth) in BENCH_POSITIONS {
+        let size = size_of_tps(tps);
+        let mut game = new_game(size, Options::default(size).unwrap()).unwrap();
+        game.set_position(tps).unwrap();
+
+        let time = Instant::now();
+        let (eval, action) = game.search(depth).unwrap();
+        let secs = time.elapsed().as_secs_f64();
+        let nodes = game.nodes();
+
+        println!("{size}x{size} depth {depth}: {action} ({eval}), {nodes} nodes in {secs:.2}s");
+        total_nodes += nodes;
+        total_secs += secs;
+    }
+
+    println!(
+        "{total_nodes} nodes in {total_secs:.2}s ({:.2} Mnps)",
+        total_nodes as f64 / total_secs / 1_000_000.,
+    );
+
+    if let Some(expected) = expected {
+        if total_nodes != expected {
+            eprintln!("signature mismatch: expected {expected} nodes");
+            process::exit(1);
+        }
+    }
+}
+
+/// Searches the bench positions of each size with the eval cache disabled and enabled, keeping
+/// the fastest of several alternating runs to dampen noise. The cache size is given as a log2.
+fn evalbench(mut args: Args) {
+    let runs = args
+        .next()
+        .map_or(DEFAULT_EVALBENCH_RUNS, |n| n.parse().unwrap());
+    let cache_size = args.next().map_or(SEARCH_PARAMS.eval_cache_size, |n| {
+        1 << n.parse::<u32>().unwrap()
+    });
+
+    for size in 3..=8 {
+        let positions: Vec<_> = BENCH_POSITIONS
+            .iter()
+            .filter(|(tps, _)| size_of_tps(tps) == size)
+            .collect();
+        if positions.is_empty() {
+            continue;
+        }
+
+        let search_all = |eval_cache_size| {
+            let mut nodes = 0;
+            let mut secs = 0.;
+            for &&(tps, depth) in &positions {
+                let opt = Options {
+                    params: SearchParams {
+                        eval_cache_size,
+                        ..SEARCH_PARAMS
+                    },
+                    ..Options::default(size).unwrap()
+                };
+                let mut game = new_game(size, opt).unwrap();
+                game.set_position(tps).unwrap();
+
+                let time = Instant::now();
+                game.search(depth).unwrap();
+                secs += time.elapsed().as_secs_f64();
+                nodes += game.nodes();
+            }
+            (nodes, secs)
+        };
+
+        let mut best = [f64::INFINITY; 2];
+        let mut node_counts = [0; 2];
+        for _ in 0..runs {
+            for (i, eval_cache_size) in [0, cache_size].into_iter().enumerate() {
+                let (nodes, secs) = search_all(eval_cache_size);
+                node_counts[i] = nodes;
+                best[i] = best[i].min(secs);
+            }
+        }
+
+        let [nodes, cached_nodes] = node_counts;
+        let [secs, cached_secs] = best;
+        println!(
+            "{size}x{size}: {nodes} nodes, {:.2} Mnps uncached, {:.2} Mnps cached ({:+.1}%)",
+            nodes as f64 / secs / 1_000_000.,
+            cached_nodes as f64 / cached_secs / 1_000_000.,
+            (secs / cached_secs - 1.) * 100.,
+        );
+
+        if nodes != cached_nodes {
+            eprintln!("node counts differ: {cached_nodes} nodes with the cache");
+            process::exit(1);
+        }
+    }
+}
+
+fn testsuite(mut args: Args) {
+    let path = args.next().expect("missing suite path");
+
+    let mut limit = util::Limit::Depth(DEFAULT_SUITE_DEPTH);
+    while let Some(arg) = args.next() {
+        let value = args.next().expect("missing option value").parse().unwrap();
+        limit = match arg.as_str() {
+            "--depth" => util::Limit::Depth(value as u32),
+            "--movetime" => util::Limit::Movetime(Duration::from_millis(value)),
+            _ => panic!(r#"unsupported option "{arg}""#),
+        };
+    }
+
+    let positions = parse_suite(&fs::read_to_string(path).unwrap()).unwrap();
+    let summary = run_suite(&positions, limit, |position, outcome| {
+        println!(
+            "{} {}: {} ({}), {} nodes in {:.2}s",
+            if outcome.passed { "pass" } else { "FAIL" },
+            position.id.as_deref().unwrap_or(&position.tps),
+            outcome.answer,
+            outcome.eval,
+            outcome.nodes,
+            outcome.time.as_secs_f64(),
+        );
+    })
+    .unwrap();
+
+    let secs = summary.time.as_secs_f64();
+    println!(
+        "{}/{} passed, {} nodes in {secs:.2}s ({:.2} Mnps)",
+        summary.passed,
+        summary.passed + summary.failed,
+        summary.nodes,
+        summary.nodes as f64 / secs / 1_000_000.,
+    );
+}
+
+fn playtak(mut args: Args) {
+    let mut settings = playtak::Settings {
+        user: args.next().expect("missing user"),
+        games: 1,
+        ..Default::default()
+    };
+    let mut server = DEFAULT_PLAYTAK_SERVER.to_string();
+    let mut seek = None;
+    let mut color = 'A';
+    let mut half_komi = 0;
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().expect("missing option value");
+        match arg.as_str() {
+            "--password" => settings.password = Some(value()),
+            "--server" => server = value(),
+            "--seek" => {
+                let size = value().parse().unwrap();
+                let tc = value();
+                let (time, increment) = tc.split_once('+').expect("malformed time control");
+                seek = Some((size, time.parse().unwrap(), increment.parse().unwrap()));
+            }
+            "--color" => color = value().parse().unwrap(),
+            "--komi" => half_komi = value().parse().unwrap(),
+            "--accept" => settings.accept = Some(value()),
+            "--games" => settings.games = value().parse().unwrap(),
+            "--depth" => settings.depth = Some(value().parse().unwrap()),
+            _ => panic!(r#"unsupported option "{arg}""#),
+        }
+    }
+
+    settings.seek = seek.map(|(size, time, increment)| playtak::Seek {
+        size,
+        time,
+        increment,
+        color,
+        half_komi,
+    });
+
+    let stream = TcpStream::connect(&server).expect("could not connect to server");
+    stream.set_read_timeout(Some(PLAYTAK_KEEPALIVE)).unwrap();
+    let reader = BufReader::new(stream.try_clone().unwrap());
+
+    if let Err(e) = playtak::run(reader, stream, &settings, |line| println!("{line}")) {
+        println!("disconnected: {e:?}");
+    }
+}
+
 fn help() {
     println!(
         r#"usage:
-    tei
+    tei [size]
     perft "<tps>"
-    search "<tps>"
-    showmatch "<tps>"
-    hashtest "<tps>""#
+    search "<tps>" [tablebase]
+    eval "<tps>"
+    show "<tps>"
+    prove "<tps>" [nodes]
+    solve "<tps>" <tablebase> [positions]    (reduced reserves or late positions only, not the full 3x3 game)
+    showmatch "<tps>" [ptn]
+    hashtest "<tps>"
+    bench [signature]
+    evalbench [runs] [cache size log2]
+    tournament <size> --engine "<command>" [--name <name>] [--option <name>=<value>]... --engine ... [--tc <base>+<inc>] [--rounds <n>] [--gauntlet] [--komi <half komi>] [--openings <file>] [--seed <n>] [--ptn <file>]
+    tune <dataset>
+    spsa [size] [iterations] [seed]
+    match <size> "<config>" "<config>" [--pairs <n>] [--threads <n>] [--seed <n>] [--openings <file>] [--sprt <elo0> <elo1>]
+    datagen <size> <games> <output> [threads] [nodes:<n>|depth:<n>] [seed] [all|quiet]
+    book build <size> <output> <ptn>... [--plies <n>] [--min-games <n>]
+    analyze <ptn> [--depth <n> | --movetime <ms>] [--output <ptn>]
+    testsuite <file> [--depth <n> | --movetime <ms>]
+    play <size> [--color <white|black>] [--depth <n> | --movetime <ms>] [--komi <half komi>] [--tps "<tps>"]
+    playtak <user> [--password <password>] [--server <address>] [--seek <size> <time>+<inc>] [--color <W|B|A>] [--komi <half komi>] [--accept <user>] [--games <n>] [--depth <n>]"#
     );
 }
 
-fn make_game(mut args: Args) -> Box<dyn Game> {
+fn make_game(args: &mut Args) -> Box<dyn Game> {
     let time = Instant::now();
     let tps = &args.next().unwrap();
     let size = size_of_tps(tps);
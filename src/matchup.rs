@@ -0,0 +1,368 @@
+use std::{
+    env::Args,
+    fs,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering::Relaxed},
+        mpsc::channel,
+    },
+    thread::scope,
+    time::{Duration, Instant},
+};
+
+use cataklysm::{eval_params, new_game};
+use common::{
+    game::*,
+    params::{EvalParams, EvalWeights, SearchParams, SEARCH_PARAMS},
+};
+
+use cataklysm::util::{self, elo, random_opening, think, time_budget, Rng};
+
+const DEFAULT_PAIRS: u64 = 100;
+const DEFAULT_THREADS: usize = 1;
+const DEFAULT_SEED: u64 = 1;
+const DEFAULT_LIMIT: Limit = Limit::Nodes(20_000);
+
+const MAX_PLIES: u32 = 300;
+const RANDOM_PLIES: usize = 4;
+const TT_SIZE: usize = 1 << 20;
+
+const SPRT_ALPHA: f64 = 0.05;
+const SPRT_BETA: f64 = 0.05;
+
+#[derive(Clone, Copy)]
+enum Limit {
+    Nodes(u64),
+    Depth(u32),
+    Movetime(Duration),
+    /// Base time and increment per move.
+    Clock(Duration, Duration),
+}
+
+#[derive(Clone, Copy)]
+struct Config {
+    params: SearchParams,
+    eval: EvalParams,
+    limit: Limit,
+}
+
+impl Config {
+    /// Reads a comma-separated list of `key=value` overrides, where a key is a field of
+    /// [`SearchParams`], `max_dist_offset`, a field of [`EvalWeights`] prefixed by `opening.` or
+    /// `endgame.`, or a limit: `nodes`, `depth`, `movetime` in milliseconds or `tc` as
+    /// `<base>+<increment>` in seconds.
+    fn parse(s: &str, size: usize) -> Option<Self> {
+        let mut config = Self {
+            params: SearchParams {
+                tt_size: TT_SIZE,
+                ..SEARCH_PARAMS
+            },
+            eval: eval_params(size)?,
+            limit: DEFAULT_LIMIT,
+        };
+
+        for entry in s.split(',').filter(|e| !e.is_empty()) {
+            let (key, value) = entry.split_once('=')?;
+            config.set(key.trim(), value.trim())?;
+        }
+
+        Some(config)
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Option<()> {
+        macro_rules! fields {
+            ($target:expr, $key:expr, $($field:ident),*) => {
+                match $key {
+                    $(stringify!($field) => $target.$field = value.parse().ok()?,)*
+                    _ => return None,
+                }
+            };
+        }
+
+        let millis = |ms: &str| ms.parse().ok().map(Duration::from_millis);
+        let secs = |s: &str| s.parse().ok().map(Duration::from_secs_f64);
+
+        match key.split_once('.') {
+            Some((phase, key)) => {
+                let weights: &mut EvalWeights = match phase {
+                    "opening" => &mut self.eval.opening,
+                    "endgame" => &mut self.eval.endgame,
+                    _ => return None,
+                };
+                fields!(
+                    weights,
+                    key,
+                    flat_count,
+                    stones_left,
+                    caps_left,
+                    total_dist,
+                    smallest_dist,
+                    side_to_move,
+                    reserves,
+                    captives,
+                    cap_stack
+                )
+            }
+            None => match key {
+                "nodes" => self.limit = Limit::Nodes(value.parse().ok()?),
+                "depth" => self.limit = Limit::Depth(value.parse().ok()?),
+                "movetime" => self.limit = Limit::Movetime(millis(value)?),
+                "tc" => {
+                    let (base, inc) = value.split_once('+')?;
+                    self.limit = Limit::Clock(secs(base)?, secs(inc)?);
+                }
+                "max_dist_offset" => self.eval.max_dist_offset = value.parse().ok()?,
+                _ => fields!(
+                    self.params,
+                    key,
+                    aspiration_window,
+                    aspiration_scaling,
+                    aspiration_attempts,
+                    use_pvs,
+                    nmp_factor,
+                    nmp_fudge,
+                    nmp_eval_margin,
+                    endgame_reserves,
+                    endgame_empty,
+                    tt_size,
+                    eval_cache_size
+                ),
+            },
+        }
+
+        Some(())
+    }
+}
+
+/// Wins, draws and losses of the first configuration.
+#[derive(Clone, Copy, Default)]
+struct Score {
+    wins: u64,
+    draws: u64,
+    losses: u64,
+}
+
+impl Score {
+    fn add(&mut self, score: f64) {
+        match score {
+            s if s > 0.5 => self.wins += 1,
+            s if s < 0.5 => self.losses += 1,
+            _ => self.draws += 1,
+        }
+    }
+
+    fn games(&self) -> u64 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// Mean score and the variance of a single game's score.
+    fn stats(&self) -> (f64, f64) {
+        let n = self.games() as f64;
+        let [w, d, l] = [self.wins, self.draws, self.losses].map(|x| x as f64 / n);
+        let mean = w + d / 2.;
+        let var = w * (1. - mean).powi(2) + d * (0.5 - mean).powi(2) + l * mean.powi(2);
+        (mean, var)
+    }
+
+    /// Elo difference with the bounds of its 95% confidence interval.
+    fn elo(&self) -> (f64, f64, f64) {
+        let (mean, var) = self.stats();
+        let margin = 1.96 * (var / self.games() as f64).sqrt();
+        (elo(mean), elo(mean - margin), elo(mean + margin))
+    }
+
+    /// Log-likelihood ratio of the hypotheses that the Elo difference is `elo1` rather than
+    /// `elo0`, approximating the distribution of the score as normal.
+    fn llr(&self, elo0: f64, elo1: f64) -> f64 {
+        let (mean, var) = self.stats();
+        if var == 0. {
+            return 0.;
+        }
+
+        let [s0, s1] = [elo0, elo1].map(|e| 1. / (1. + 10f64.powf(-e / 400.)));
+        self.games() as f64 * (s1 - s0) * (2. * mean - s0 - s1) / (2. * var)
+    }
+}
+
+/// Plays pairs of games between two configurations from the same openings with colors swapped,
+/// and reports the score of the first with its Elo difference.
+pub fn run(mut args: Args) {
+    let size: usize = args.next().expect("missing size").parse().unwrap();
+    let configs = [(); 2].map(|_| {
+        let config = args.next().expect("missing configuration");
+        Config::parse(&config, size).expect("invalid configuration")
+    });
+
+    let mut pairs = DEFAULT_PAIRS;
+    let mut threads = DEFAULT_THREADS;
+    let mut seed = DEFAULT_SEED;
+    let mut openings = None;
+    let mut sprt = None;
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().expect("missing option value");
+        match arg.as_str() {
+            "--pairs" => pairs = value().parse().unwrap(),
+            "--threads" => threads = value().parse().unwrap(),
+            "--seed" => seed = value().parse().unwrap(),
+            "--openings" => openings = Some(value()),
+            "--sprt" => {
+                let elo0: f64 = value().parse().unwrap();
+                let elo1: f64 = value().parse().unwrap();
+                sprt = Some((elo0, elo1));
+            }
+            _ => panic!(r#"unsupported option "{arg}""#),
+        }
+    }
+
+    // Openings are TPS, one per line
+    let openings = openings.map(|path| {
+        fs::read_to_string(path)
+            .expect("could not read openings")
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .map(String::from)
+            .collect::<Vec<_>>()
+    });
+
+    let [lower, upper] =
+        [SPRT_BETA / (1. - SPRT_ALPHA), (1. - SPRT_BETA) / SPRT_ALPHA].map(f64::ln);
+
+    let next_pair = AtomicU64::new(0);
+    let stop = AtomicBool::new(false);
+    let (tx, rx) = channel();
+
+    scope(|scope| {
+        for _ in 0..threads {
+            let tx = tx.clone();
+            let (next_pair, stop, openings) = (&next_pair, &stop, &openings);
+
+            scope.spawn(move || loop {
+                let i = next_pair.fetch_add(1, Relaxed);
+                if i >= pairs || stop.load(Relaxed) {
+                    break;
+                }
+
+                let mut game = new_game(size, Options::default(size).unwrap()).unwrap();
+                match openings {
+                    Some(openings) => {
+                        let tps = &openings[i as usize % openings.len()];
+                        game.set_position(tps).expect("invalid opening");
+                    }
+                    None => {
+                        let mut rng = Rng(seed ^ i.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+                        for mv in random_opening(size, RANDOM_PLIES, &mut rng) {
+                            let mv = game.parser()(&mv).unwrap();
+                            game.play(mv).unwrap();
+                        }
+                    }
+                }
+                let start = game.tps().to_string();
+
+                let first = play_game(size, &start, configs[0], configs[1]);
+                let second = 1. - play_game(size, &start, configs[1], configs[0]);
+                if tx.send([first, second]).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(tx);
+
+        let mut score = Score::default();
+        for results in rx {
+            for result in results {
+                score.add(result);
+            }
+
+            let (elo, low, high) = score.elo();
+            let mut line = format!(
+                "{} games: +{} ={} -{}, elo {elo:+.1} [{low:+.1}, {high:+.1}]",
+                score.games(),
+                score.wins,
+                score.draws,
+                score.losses,
+            );
+
+            if let Some((elo0, elo1)) = sprt {
+                let llr = score.llr(elo0, elo1);
+                line += &format!(", llr {llr:.2} [{lower:.2}, {upper:.2}]");
+
+                if !stop.load(Relaxed) && (llr <= lower || llr >= upper) {
+                    stop.store(true, Relaxed);
+                    line += if llr >= upper {
+                        ", H1 accepted"
+                    } else {
+                        ", H0 accepted"
+                    };
+                }
+            }
+
+            println!("{line}");
+        }
+    });
+}
+
+/// Plays a game from a position and returns the score of white.
+fn play_game(size: usize, start: &str, white: Config, black: Config) -> f64 {
+    let configs = [white, black];
+    let mut players = configs.map(|config| {
+        let mut game = new_game(
+            size,
+            Options {
+                params: config.params,
+                eval: Some(config.eval),
+                ..Options::default(size).unwrap()
+            },
+        )
+        .unwrap();
+        game.set_position(start).unwrap();
+        game
+    });
+
+    let mut clocks = configs.map(|config| match config.limit {
+        Limit::Clock(base, _) => base,
+        _ => Duration::MAX,
+    });
+
+    for ply in 0.. {
+        if let Some(result) = players[0].result() {
+            return result.white_score();
+        }
+
+        if ply >= MAX_PLIES {
+            return GameResult::Draw.white_score();
+        }
+
+        let side = players[0].active_color() as usize;
+        let game = &mut players[side];
+        let limit = configs[side].limit;
+
+        let search_limit = match limit {
+            Limit::Nodes(nodes) => util::Limit::Nodes(nodes),
+            Limit::Depth(depth) => util::Limit::Depth(depth),
+            Limit::Movetime(time) => util::Limit::Movetime(time),
+            Limit::Clock(_, increment) => {
+                util::Limit::Movetime(time_budget(&**game, clocks[side], increment))
+            }
+        };
+
+        let time = Instant::now();
+        let (_, _, best) = think(&mut **game, search_limit);
+        let best = best.to_string();
+        let elapsed = time.elapsed();
+
+        if let Limit::Clock(_, increment) = limit {
+            let Some(left) = clocks[side].checked_sub(elapsed) else {
+                // Lost on time
+                return side as f64;
+            };
+            clocks[side] = left + increment;
+        }
+
+        for game in &mut players {
+            let mv = game.parser()(&best).unwrap();
+            game.play(mv).unwrap();
+        }
+    }
+
+    unreachable!()
+}
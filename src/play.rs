@@ -0,0 +1,141 @@
+use std::{
+    env::Args,
+    fs,
+    io::{stdin, stdout, Write},
+    time::Duration,
+};
+
+use cataklysm::{
+    ptn::PtnGame,
+    util::{think, Limit},
+};
+use common::pair::{BLACK, WHITE};
+
+const DEFAULT_LIMIT: Limit = Limit::Movetime(Duration::from_secs(2));
+const ENGINE_NAME: &str = "cataklysm";
+
+/// Plays a game against a human on the terminal, reading moves in PTN and commands from stdin.
+pub fn run(mut args: Args) {
+    let size: usize = args.next().expect("missing size").parse().unwrap();
+
+    let mut human = WHITE;
+    let mut limit = DEFAULT_LIMIT;
+    let mut half_komi = 0;
+    let mut tps = None;
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().expect("missing option value");
+        match arg.as_str() {
+            "--color" => {
+                human = match value().as_str() {
+                    "white" => WHITE,
+                    "black" => BLACK,
+                    color => panic!(r#"unsupported color "{color}""#),
+                }
+            }
+            "--depth" => limit = Limit::Depth(value().parse().unwrap()),
+            "--movetime" => {
+                limit = Limit::Movetime(Duration::from_millis(value().parse().unwrap()))
+            }
+            "--komi" => half_komi = value().parse().unwrap(),
+            "--tps" => tps = Some(value()),
+            _ => panic!(r#"unsupported option "{arg}""#),
+        }
+    }
+
+    let mut ptn = PtnGame::new(size, half_komi);
+    let [white, black] = if human == WHITE {
+        ["human", ENGINE_NAME]
+    } else {
+        [ENGINE_NAME, "human"]
+    };
+    ptn.set_tag("Player1", white);
+    ptn.set_tag("Player2", black);
+    if let Some(tps) = tps {
+        ptn.set_tag("TPS", tps);
+    }
+
+    let opt = ptn.options().expect("unsupported size");
+    let mut game = ptn.replay(opt, |_, _| {}).expect("invalid position");
+
+    println!(r#"enter moves in PTN, "undo", "save <file>" or "quit""#);
+    let mut lines = stdin().lines().map_while(Result::ok);
+    let mut redraw = true;
+    loop {
+        let result = game.result();
+        if redraw {
+            println!("{}", game.board());
+            if let Some(result) = result {
+                println!("game finished: {result}");
+                ptn.set_result(result);
+            }
+            redraw = false;
+        }
+
+        if result.is_none() && game.active_color() != human {
+            let (eval, depth, action) = think(&mut *game, limit);
+            println!("{ENGINE_NAME} plays {action} (eval {eval}, depth {depth})");
+            ptn.push(&action, Vec::new()).unwrap();
+            game.play(action).unwrap();
+            redraw = true;
+            continue;
+        }
+
+        let line = loop {
+            print!("> ");
+            stdout().flush().unwrap();
+            let Some(line) = lines.next() else {
+                return;
+            };
+            let line = line.trim().to_string();
+            if !line.is_empty() {
+                break line;
+            }
+        };
+
+        let mut tokens = line.split_whitespace();
+        match tokens.next().unwrap() {
+            "quit" => return,
+            "undo" => {
+                // Take back the last move of the human along with the replies to it
+                let start_ply = game.ply() as usize - ptn.moves().len();
+                let Some(last) = (0..ptn.moves().len())
+                    .rev()
+                    .find(|&i| (start_ply + i) % 2 == human as usize)
+                else {
+                    println!("there is no move to undo");
+                    continue;
+                };
+
+                ptn.truncate(last);
+                game = ptn.replay(ptn.options().unwrap(), |_, _| {}).unwrap();
+                redraw = true;
+            }
+            "save" => {
+                let Some(path) = tokens.next() else {
+                    println!("missing file to save to");
+                    continue;
+                };
+                match fs::write(path, ptn.to_string()) {
+                    Ok(()) => println!("saved to {path}"),
+                    Err(e) => println!("could not save to {path}: {e}"),
+                }
+            }
+            _ if result.is_some() => println!(r#"the game is over, "undo" to take back a move"#),
+            mv => match game.parser()(mv) {
+                Err(_) => {
+                    println!(r#"could not read "{mv}", expected PTN such as "Sc3" or "3a1+12""#)
+                }
+                Ok(action) => {
+                    let text = action.to_string();
+                    match game.play(action) {
+                        Ok(()) => {
+                            ptn.push(text, Vec::new()).unwrap();
+                            redraw = true;
+                        }
+                        Err(e) => println!("{mv} is illegal: {e}"),
+                    }
+                }
+            },
+        }
+    }
+}
@@ -0,0 +1,274 @@
+use std::{
+    io::{self, BufRead, ErrorKind, Write},
+    time::Duration,
+};
+
+use common::{game::*, pair::Pair};
+
+use crate::{
+    new_game,
+    util::{think, time_budget, Limit},
+};
+
+const CLIENT_NAME: &str = "cataklysm";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayTakError {
+    Io,
+    Disconnected,
+    Authentication,
+    UnsupportedGame,
+    /// The server sent a move which could not be read or played.
+    IllegalMove,
+}
+
+impl From<io::Error> for PlayTakError {
+    fn from(_: io::Error) -> Self {
+        Self::Io
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Seek {
+    pub size: usize,
+    /// Initial time and increment, in seconds.
+    pub time: u32,
+    pub increment: u32,
+    /// `W`, `B` or `A` for any color.
+    pub color: char,
+    pub half_komi: i32,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Settings {
+    pub user: String,
+    /// `None` logs in as a guest.
+    pub password: Option<String>,
+    pub seek: Option<Seek>,
+    /// Accepts seeks from the player with this name.
+    pub accept: Option<String>,
+    /// Number of games to play before logging out.
+    pub games: u32,
+    /// Searches to a fixed depth instead of managing the clock.
+    pub depth: Option<u32>,
+}
+
+/// A game in progress on the server.
+struct Session {
+    id: String,
+    game: Box<dyn Game>,
+    color: bool,
+    clocks: Pair<Duration>,
+    increment: Duration,
+}
+
+struct Client<'a, R, W, L> {
+    reader: R,
+    writer: W,
+    settings: &'a Settings,
+    log: L,
+    /// Increment of the seek the next game is expected to come from.
+    increment: Duration,
+}
+
+impl<R: BufRead, W: Write, L: FnMut(&str)> Client<'_, R, W, L> {
+    fn send(&mut self, line: &str) -> Result<(), PlayTakError> {
+        (self.log)(&format!("> {line}"));
+        writeln!(self.writer, "{line}")?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Reads the next line, sending a keepalive whenever the read times out.
+    fn recv(&mut self) -> Result<String, PlayTakError> {
+        let mut line = String::new();
+        loop {
+            match self.reader.read_line(&mut line) {
+                Ok(0) => return Err(PlayTakError::Disconnected),
+                Ok(_) => break,
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    self.send("PING")?;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        let line = line.trim_end().to_string();
+        (self.log)(&format!("< {line}"));
+        Ok(line)
+    }
+
+    fn login(&mut self) -> Result<(), PlayTakError> {
+        self.send(&format!("Client {CLIENT_NAME}"))?;
+        match &self.settings.password {
+            Some(password) => {
+                let login = format!("Login {} {password}", self.settings.user);
+                self.send(&login)?
+            }
+            None => self.send("Login Guest")?,
+        }
+
+        loop {
+            let line = self.recv()?;
+            if line.starts_with("Welcome ") {
+                return Ok(());
+            }
+            if line.starts_with("Authentication failure") {
+                return Err(PlayTakError::Authentication);
+            }
+        }
+    }
+
+    fn seek(&mut self) -> Result<(), PlayTakError> {
+        let settings = self.settings;
+        if let Some(seek) = &settings.seek {
+            let opt = Options::default(seek.size).ok_or(PlayTakError::UnsupportedGame)?;
+            let line = format!(
+                "Seek {} {} {} {} {} {} {} 0 0",
+                seek.size,
+                seek.time,
+                seek.increment,
+                seek.color,
+                seek.half_komi,
+                opt.start_stones.white,
+                opt.start_caps.white,
+            );
+            self.increment = Duration::from_secs(seek.increment as _);
+            self.send(&line)?;
+        }
+        Ok(())
+    }
+
+    fn run(&mut self) -> Result<(), PlayTakError> {
+        self.login()?;
+        self.seek()?;
+
+        let mut played = 0;
+        let mut session = None::<Session>;
+
+        while played < self.settings.games {
+            let line = self.recv()?;
+            let mut tokens = line.split_whitespace();
+
+            match tokens.next() {
+                Some("Seek") if session.is_none() && tokens.next() == Some("new") => {
+                    let fields = tokens.collect::<Vec<_>>();
+                    // Seek new <id> <name> <size> <time> <increment> ...
+                    if let [id, name, _size, _time, increment, ..] = fields[..] {
+                        if self.settings.accept.as_deref() == Some(name) {
+                            self.increment = Duration::from_secs(increment.parse().unwrap_or(0));
+                            self.send(&format!("Accept {id}"))?;
+                        }
+                    }
+                }
+                Some("Game") if tokens.next() == Some("Start") => {
+                    session = Some(self.start(tokens.collect())?);
+                    self.play_if_our_turn(session.as_mut().unwrap())?;
+                }
+                Some(prefix) if prefix.starts_with("Game#") => {
+                    let Some(s) = session.as_mut().filter(|s| prefix[5..] == s.id) else {
+                        continue;
+                    };
+
+                    match tokens.next() {
+                        Some("P" | "M") => {
+                            let action = s.game.playtak_parser()(&line[prefix.len()..])
+                                .map_err(|_| PlayTakError::IllegalMove)?;
+                            s.game.play(action).map_err(|_| PlayTakError::IllegalMove)?;
+                            self.play_if_our_turn(s)?;
+                        }
+                        Some("Time") => {
+                            let mut secs = tokens.filter_map(|t| t.parse::<f64>().ok());
+                            if let (Some(white), Some(black)) = (secs.next(), secs.next()) {
+                                s.clocks = Pair::new(white, black).map(Duration::from_secs_f64);
+                            }
+                        }
+                        Some("Timems") => {
+                            let mut ms = tokens.filter_map(|t| t.parse::<u64>().ok());
+                            if let (Some(white), Some(black)) = (ms.next(), ms.next()) {
+                                s.clocks = Pair::new(white, black).map(Duration::from_millis);
+                            }
+                        }
+                        Some(over) if over == "Over" || over.starts_with("Abandoned") => {
+                            session = None;
+                            played += 1;
+                            if played < self.settings.games {
+                                self.seek()?;
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        self.send("quit")
+    }
+
+    /// Sets up a game from the fields of a `Game Start` message:
+    /// `<id> <size> <white> vs <black> <color> <time> [<komi> <stones> <caps> ...]`.
+    fn start(&mut self, fields: Vec<&str>) -> Result<Session, PlayTakError> {
+        let [id, size, _white, _vs, _black, color, time, ref rest @ ..] = fields[..] else {
+            return Err(PlayTakError::UnsupportedGame);
+        };
+
+        let size = size.parse().map_err(|_| PlayTakError::UnsupportedGame)?;
+        let mut opt = Options::default(size).ok_or(PlayTakError::UnsupportedGame)?;
+        let mut rest = rest.iter().map(|field| field.parse::<u32>().ok());
+        if let Some(Some(half_komi)) = rest.next() {
+            opt.half_komi = half_komi as i32;
+        }
+        if let Some(Some(stones)) = rest.next() {
+            opt.start_stones = Pair::both(stones);
+        }
+        if let Some(Some(caps)) = rest.next() {
+            opt.start_caps = Pair::both(caps);
+        }
+
+        let time = Duration::from_secs(time.parse().map_err(|_| PlayTakError::UnsupportedGame)?);
+        Ok(Session {
+            id: id.to_string(),
+            game: new_game(size, opt).map_err(|_| PlayTakError::UnsupportedGame)?,
+            color: color == "black",
+            clocks: Pair::both(time),
+            increment: self.increment,
+        })
+    }
+
+    fn play_if_our_turn(&mut self, s: &mut Session) -> Result<(), PlayTakError> {
+        if s.game.active_color() != s.color || s.game.result().is_some() {
+            return Ok(());
+        }
+
+        // Search to the depth if one is given, and otherwise within a share of the remaining time
+        let limit = match self.settings.depth {
+            Some(depth) => Limit::Depth(depth),
+            None => Limit::Movetime(time_budget(&*s.game, s.clocks[s.color], s.increment)),
+        };
+        let mv = think(&mut *s.game, limit).2.to_string();
+        let parse = s.game.parser();
+        let server = s.game.playtak_move(parse(&mv).unwrap()).to_string();
+        s.game.play(parse(&mv).unwrap()).unwrap();
+
+        self.send(&format!("Game#{} {server}", s.id))
+    }
+}
+
+/// Plays games on a PlayTak server over the connection, calling `log` with each line sent or
+/// received, prefixed by `> ` or `< ` respectively.
+pub fn run(
+    reader: impl BufRead,
+    writer: impl Write,
+    settings: &Settings,
+    log: impl FnMut(&str),
+) -> Result<(), PlayTakError> {
+    Client {
+        reader,
+        writer,
+        settings,
+        log,
+        increment: Duration::ZERO,
+    }
+    .run()
+}
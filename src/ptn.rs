@@ -0,0 +1,234 @@
+use std::{fmt, str::FromStr};
+
+use common::{
+    game::*,
+    pair::{Pair, WHITE},
+};
+
+use takparse::{GameResult as PtnResult, Move as PtnMove, Ptn, Tag, WinReason};
+
+use crate::new_game;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PtnError {
+    Parse,
+    UnsupportedSize,
+    InvalidPosition,
+    /// The move with the given index could not be parsed or played.
+    IllegalMove(usize),
+}
+
+/// A game in Portable Tak Notation.
+///
+/// Moves and the result are checked as they are added, so that the game can always be written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PtnGame {
+    pub tags: Vec<(String, String)>,
+    moves: Vec<PtnMove>,
+    /// Comments on the game as a whole, followed by those on each move.
+    pub comments: Vec<Vec<String>>,
+    /// Result, which may also record a resignation or a loss on time.
+    result: Option<PtnResult>,
+}
+
+impl PtnGame {
+    pub fn new(size: usize, half_komi: i32) -> Self {
+        let mut game = Self {
+            tags: Vec::new(),
+            moves: Vec::new(),
+            comments: vec![Vec::new()],
+            result: None,
+        };
+
+        game.set_tag("Size", size.to_string());
+        if half_komi != 0 {
+            let komi = half_komi as f64 / 2.;
+            game.set_tag("Komi", komi.to_string());
+        }
+        game
+    }
+
+    pub fn tag(&self, key: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn set_tag(&mut self, key: &str, value: impl Into<String>) {
+        let value = value.into();
+        match self.tags.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = value,
+            None => self.tags.push((key.to_string(), value)),
+        }
+    }
+
+    pub fn size(&self) -> Option<usize> {
+        self.tag("Size")?.parse().ok()
+    }
+
+    pub fn half_komi(&self) -> Option<i32> {
+        let komi = self.tag("Komi")?.parse::<f64>().ok()? * 2.;
+        (komi.fract() == 0.).then_some(komi as i32)
+    }
+
+    pub fn start_tps(&self) -> Option<&str> {
+        self.tag("TPS")
+    }
+
+    pub fn moves(&self) -> &[PtnMove] {
+        &self.moves
+    }
+
+    /// Appends a move, which must be in PTN.
+    pub fn push(&mut self, mv: impl fmt::Display, comments: Vec<String>) -> Result<(), PtnError> {
+        let mv = mv
+            .to_string()
+            .parse()
+            .map_err(|_| PtnError::IllegalMove(self.moves.len()))?;
+
+        self.moves.push(mv);
+        self.comments.push(comments);
+        Ok(())
+    }
+
+    /// Keeps the first `len` moves with their comments, and clears the result.
+    pub fn truncate(&mut self, len: usize) {
+        self.moves.truncate(len);
+        self.comments.truncate(len + 1);
+        self.result = None;
+        self.tags.retain(|(key, _)| key != "Result");
+    }
+
+    pub fn result(&self) -> Option<PtnResult> {
+        self.result
+    }
+
+    pub fn set_result(&mut self, result: GameResult) {
+        self.set_ptn_result(match result {
+            GameResult::Road(color) => win(color, WinReason::Road),
+            GameResult::Flat(color) => win(color, WinReason::Flat),
+            GameResult::Draw => PtnResult::Draw,
+        });
+    }
+
+    /// Records a win by other means than a road or flats, such as resignation or time.
+    pub fn set_other_win(&mut self, color: bool) {
+        self.set_ptn_result(win(color, WinReason::Other));
+    }
+
+    fn set_ptn_result(&mut self, result: PtnResult) {
+        self.result = Some(result);
+        self.set_tag("Result", result.to_string());
+    }
+
+    /// Returns the game options given by the tags.
+    pub fn options(&self) -> Option<Options> {
+        let mut opt = Options::default(self.size()?)?;
+
+        if let Some(flats) = self.tag("Flats") {
+            opt.start_stones = Pair::both(flats.parse().ok()?);
+        }
+        if let Some(caps) = self.tag("Caps") {
+            opt.start_caps = Pair::both(caps.parse().ok()?);
+        }
+        if self.tag("Komi").is_some() {
+            opt.half_komi = self.half_komi()?;
+        }
+
+        Some(opt)
+    }
+
+    /// Replays the game, calling `f` with the index of each move before it is played.
+    pub fn replay(
+        &self,
+        opt: Options,
+        mut f: impl FnMut(&mut dyn Game, usize),
+    ) -> Result<Box<dyn Game>, PtnError> {
+        let size = self.size().ok_or(PtnError::UnsupportedSize)?;
+        let mut game = new_game(size, opt).map_err(|_| PtnError::UnsupportedSize)?;
+
+        if let Some(tps) = self.start_tps() {
+            game.set_position(tps)
+                .map_err(|_| PtnError::InvalidPosition)?;
+        }
+
+        let parse = game.parser();
+        for (i, mv) in self.moves.iter().enumerate() {
+            f(&mut *game, i);
+
+            let action = parse(&mv.to_string()).map_err(|_| PtnError::IllegalMove(i))?;
+            game.play(action).map_err(|_| PtnError::IllegalMove(i))?;
+        }
+
+        Ok(game)
+    }
+}
+
+impl FromStr for PtnGame {
+    type Err = PtnError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let ptn = s.parse::<Ptn>().map_err(|_| PtnError::Parse)?;
+
+        Ok(Self {
+            tags: ptn
+                .tags()
+                .iter()
+                .map(|tag| (tag.key().to_string(), tag.value().to_string()))
+                .collect(),
+            moves: ptn.moves().to_vec(),
+            comments: ptn.comments().to_vec(),
+            result: ptn.result(),
+        })
+    }
+}
+
+impl fmt::Display for PtnGame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // There is a group of comments for the game and one for each move
+        let mut comments = self.comments.clone();
+        comments.resize(self.moves.len() + 1, Vec::new());
+
+        let ptn = Ptn::new(
+            self.tags.iter().map(|(k, v)| Tag::new(k, v)).collect(),
+            self.moves.clone(),
+            comments,
+            self.result,
+        );
+        write!(f, "{ptn}")
+    }
+}
+
+fn win(color: bool, reason: WinReason) -> PtnResult {
+    if color == WHITE {
+        PtnResult::White(reason)
+    } else {
+        PtnResult::Black(reason)
+    }
+}
+
+/// Splits a collection of games at tags which follow moves, and parses each of them.
+pub fn parse_collection(text: &str) -> Vec<Result<PtnGame, PtnError>> {
+    let mut games = Vec::new();
+    let mut game = String::new();
+    let mut has_moves = false;
+
+    for line in text.lines() {
+        let line = line.trim();
+        if line.starts_with('[') && has_moves {
+            games.push(std::mem::take(&mut game));
+            has_moves = false;
+        }
+
+        has_moves |= !line.is_empty() && !line.starts_with('[');
+        game.push_str(line);
+        game.push('\n');
+    }
+
+    if !game.trim().is_empty() {
+        games.push(game);
+    }
+
+    games.iter().map(|game| game.parse()).collect()
+}
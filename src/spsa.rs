@@ -0,0 +1,180 @@
+use std::env::Args;
+
+use cataklysm::new_game;
+use common::{
+    game::*,
+    params::{SearchParams, SEARCH_PARAMS},
+};
+
+use cataklysm::util::{random_opening, think, Limit, Rng};
+
+const DEFAULT_SIZE: usize = 5;
+const DEFAULT_ITERATIONS: u32 = 1000;
+const DEFAULT_SEED: u64 = 1;
+
+const NODES_PER_MOVE: u64 = 20_000;
+const MAX_PLIES: u32 = 300;
+const RANDOM_PLIES: usize = 4;
+const TT_SIZE: usize = 1 << 16;
+
+const ALPHA: f64 = 0.602;
+const GAMMA: f64 = 0.101;
+const R_END: f64 = 0.002;
+
+/// A tuned search parameter, represented as a real number.
+struct Tunable {
+    name: &'static str,
+    min: f64,
+    max: f64,
+    /// Perturbation size at the final iteration.
+    c_end: f64,
+    get: fn(&SearchParams) -> f64,
+    set: fn(&mut SearchParams, f64),
+}
+
+macro_rules! tunable {
+    ($field:ident, $min:expr, $max:expr, $c_end:expr) => {
+        Tunable {
+            name: stringify!($field),
+            min: $min as f64,
+            max: $max as f64,
+            c_end: $c_end,
+            get: |p| p.$field as f64,
+            set: |p, v| p.$field = v.round() as _,
+        }
+    };
+}
+
+const TUNABLES: &[Tunable] = &[
+    tunable!(aspiration_window, 1, 200, 5.),
+    tunable!(aspiration_scaling, 1, 16, 1.),
+    tunable!(aspiration_attempts, 0, 8, 1.),
+    Tunable {
+        name: "use_pvs",
+        min: 0.,
+        max: 1.,
+        c_end: 0.6,
+        get: |p| p.use_pvs as u8 as f64,
+        set: |p, v| p.use_pvs = v >= 0.5,
+    },
+    tunable!(nmp_factor, 1, 6, 1.),
+    tunable!(nmp_fudge, -10, 30, 2.),
+    tunable!(nmp_eval_margin, -20, 50, 3.),
+];
+
+pub fn run(mut args: Args) {
+    let size = args.next().map_or(DEFAULT_SIZE, |s| s.parse().unwrap());
+    let iterations = args
+        .next()
+        .map_or(DEFAULT_ITERATIONS, |n| n.parse().unwrap());
+    let seed = args.next().map_or(DEFAULT_SEED, |n| n.parse().unwrap());
+
+    println!("spsa: size {size}, {iterations} iterations, seed {seed}");
+
+    let mut rng = Rng(seed);
+    let mut theta = TUNABLES
+        .iter()
+        .map(|t| (t.get)(&SEARCH_PARAMS))
+        .collect::<Vec<_>>();
+
+    let n = iterations as f64;
+    let big_a = n / 10.;
+
+    for k in 1..=iterations {
+        let c_k = |t: &Tunable| t.c_end * n.powf(GAMMA) / (k as f64).powf(GAMMA);
+        let a_k = |t: &Tunable| {
+            R_END * t.c_end.powi(2) * (big_a + n).powf(ALPHA) / (big_a + k as f64).powf(ALPHA)
+        };
+
+        let delta = TUNABLES
+            .iter()
+            .map(|_| if rng.next_u64() & 1 == 0 { 1. } else { -1. })
+            .collect::<Vec<_>>();
+
+        let perturbed = |sign: f64| {
+            let mut params = SearchParams {
+                tt_size: TT_SIZE,
+                ..SEARCH_PARAMS
+            };
+            for ((t, &v), &d) in TUNABLES.iter().zip(&theta).zip(&delta) {
+                (t.set)(&mut params, (v + sign * c_k(t) * d).clamp(t.min, t.max));
+            }
+            params
+        };
+
+        let plus = perturbed(1.);
+        let minus = perturbed(-1.);
+
+        let opening = random_opening(size, RANDOM_PLIES, &mut rng);
+        let first = play_game(size, plus, minus, &opening);
+        let second = play_game(size, minus, plus, &opening);
+
+        // Score of the positive perturbation, in [-1, 1]
+        let result = first.white_score() - second.white_score();
+
+        for ((t, v), &d) in TUNABLES.iter().zip(&mut theta).zip(&delta) {
+            *v = (*v + a_k(t) / c_k(t) * result * d).clamp(t.min, t.max);
+        }
+
+        let values = TUNABLES
+            .iter()
+            .zip(&theta)
+            .map(|(t, v)| format!("{} {v:.3}", t.name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        println!("iteration {k}: {first} {second}, {values}");
+    }
+
+    let mut params = SEARCH_PARAMS;
+    for (t, &v) in TUNABLES.iter().zip(&theta) {
+        (t.set)(&mut params, v);
+    }
+
+    println!("pub static SEARCH_PARAMS: SearchParams = {params:#?};");
+}
+
+fn play_game(
+    size: usize,
+    white: SearchParams,
+    black: SearchParams,
+    opening: &[String],
+) -> GameResult {
+    let mut players = [white, black].map(|params| {
+        new_game(
+            size,
+            Options {
+                params,
+                ..Options::default(size).unwrap()
+            },
+        )
+        .unwrap()
+    });
+
+    for mv in opening {
+        for game in &mut players {
+            let mv = game.parser()(mv).unwrap();
+            game.play(mv).unwrap();
+        }
+    }
+
+    for ply in opening.len() as u32.. {
+        if let Some(result) = players[0].result() {
+            return result;
+        }
+
+        if ply >= MAX_PLIES {
+            return GameResult::Draw;
+        }
+
+        let game = &mut players[ply as usize % 2];
+        let (_, _, best) = think(&mut **game, Limit::Nodes(NODES_PER_MOVE));
+
+        let best = best.to_string();
+        for game in &mut players {
+            let mv = game.parser()(&best).unwrap();
+            game.play(mv).unwrap();
+        }
+    }
+
+    unreachable!()
+}
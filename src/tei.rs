@@ -1,7 +1,16 @@
-use std::{pin::Pin, thread::spawn};
+use std::{
+    fs,
+    pin::Pin,
+    thread::spawn,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
-use cataklysm::new_game;
-use common::{game::*, pair::Pair};
+use cataklysm::{eval_params, new_game, util::Rng};
+use common::{
+    game::*,
+    pair::Pair,
+    params::{EvalParams, SearchParams, SEARCH_PARAMS},
+};
 
 use tokio::{
     io::{stdin, AsyncBufReadExt, BufReader},
@@ -13,6 +22,8 @@ use tokio::{
     time::{sleep, Duration, Instant, Sleep},
 };
 
+use crate::book::Book;
+
 // FIXME
 const FOREVER: Duration = Duration::from_secs(60 * 60 * 24 * 365); // 1 year
 
@@ -27,6 +38,89 @@ const MAX_DEPTH: u32 = {
 
 const MIN_KOMI: i32 = -20;
 const MAX_KOMI: i32 = 20;
+/// Size whose eval defaults are advertised when none is given.
+pub const DEFAULT_SIZE: usize = 6;
+
+#[derive(Clone, Copy)]
+struct Params {
+    search: SearchParams,
+    eval: EvalParams,
+}
+
+/// A `spin` option backed by a parameter field.
+struct Spin {
+    name: &'static str,
+    min: i64,
+    max: i64,
+    get: fn(&Params) -> i64,
+    set: fn(&mut Params, i64),
+}
+
+macro_rules! spin {
+    ($name:literal, $($field:ident).+, $min:expr, $max:expr) => {
+        Spin {
+            name: $name,
+            min: $min,
+            max: $max,
+            get: |p| p.$($field).+ as _,
+            set: |p, v| p.$($field).+ = v as _,
+        }
+    };
+}
+
+const SPINS: &[Spin] = &[
+    spin!("AspirationWindow", search.aspiration_window, 1, 1000),
+    spin!("AspirationScaling", search.aspiration_scaling, 1, 16),
+    spin!("AspirationAttempts", search.aspiration_attempts, 0, 16),
+    spin!("NmpFactor", search.nmp_factor, 0, 8),
+    spin!("NmpFudge", search.nmp_fudge, -100, 100),
+    spin!("NmpEvalMargin", search.nmp_eval_margin, -100, 100),
+    spin!("EndgameReserves", search.endgame_reserves, 0, 50),
+    spin!("EndgameEmpty", search.endgame_empty, 0, 64),
+    Spin {
+        name: "TtSizeLog2",
+        min: 0,
+        max: 30,
+        get: |p| p.search.tt_size.ilog2() as _,
+        set: |p, v| p.search.tt_size = 1 << v,
+    },
+    Spin {
+        name: "EvalCacheSizeLog2",
+        min: 0,
+        max: 24,
+        get: |p| p.search.eval_cache_size.max(1).ilog2() as _,
+        set: |p, v| p.search.eval_cache_size = 1 << v,
+    },
+    spin!("MaxDistOffset", eval.max_dist_offset, -8, 8),
+    spin!("FlatCountOpening", eval.opening.flat_count, -1000, 1000),
+    spin!("FlatCountEndgame", eval.endgame.flat_count, -1000, 1000),
+    spin!("StonesLeftOpening", eval.opening.stones_left, -1000, 1000),
+    spin!("StonesLeftEndgame", eval.endgame.stones_left, -1000, 1000),
+    spin!("CapsLeftOpening", eval.opening.caps_left, -1000, 1000),
+    spin!("CapsLeftEndgame", eval.endgame.caps_left, -1000, 1000),
+    spin!("TotalDistOpening", eval.opening.total_dist, -1000, 1000),
+    spin!("TotalDistEndgame", eval.endgame.total_dist, -1000, 1000),
+    spin!(
+        "SmallestDistOpening",
+        eval.opening.smallest_dist,
+        -1000,
+        1000
+    ),
+    spin!(
+        "SmallestDistEndgame",
+        eval.endgame.smallest_dist,
+        -1000,
+        1000
+    ),
+    spin!("SideToMoveOpening", eval.opening.side_to_move, -1000, 1000),
+    spin!("SideToMoveEndgame", eval.endgame.side_to_move, -1000, 1000),
+    spin!("ReservesOpening", eval.opening.reserves, -1000, 1000),
+    spin!("ReservesEndgame", eval.endgame.reserves, -1000, 1000),
+    spin!("CaptivesOpening", eval.opening.captives, -1000, 1000),
+    spin!("CaptivesEndgame", eval.endgame.captives, -1000, 1000),
+    spin!("CapStackOpening", eval.opening.cap_stack, -1000, 1000),
+    spin!("CapStackEndgame", eval.endgame.cap_stack, -1000, 1000),
+];
 
 struct State {
     rx: UnboundedReceiver<Box<dyn Game>>,
@@ -37,6 +131,15 @@ struct State {
     debug: bool,
     timeout: Pin<Box<Sleep>>,
     half_komi: i32,
+    use_pvs: bool,
+    use_nnue: bool,
+    network: Option<Vec<u8>>,
+    use_book: bool,
+    book: Option<Book>,
+    rng: Rng,
+    size: usize,
+    /// Spin options in the order they were set, applied over the defaults for the board size.
+    spins: Vec<(&'static Spin, i64)>,
 }
 
 struct Search {
@@ -57,6 +160,17 @@ impl State {
             debug: false,
             timeout: Box::pin(sleep(FOREVER)),
             half_komi: 0,
+            use_pvs: SEARCH_PARAMS.use_pvs,
+            use_nnue: false,
+            network: None,
+            use_book: false,
+            book: None,
+            rng: Rng(SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_nanos() as u64),
+            size: 0,
+            spins: Vec::new(),
         }
     }
 
@@ -79,6 +193,44 @@ impl State {
         self.timeout.as_mut().reset(start + FOREVER);
     }
 
+    /// Game to inspect without aborting the search, which is only available once the search has
+    /// sent it back.
+    fn idle_game(&mut self) -> Result<&dyn Game, &'static str> {
+        if self.flag.is_some() {
+            let Ok(game) = self.rx.try_recv() else {
+                return Err("a search is running");
+            };
+
+            self.flag = None;
+            self.game = Some(game);
+            self.timeout.as_mut().reset(Instant::now() + FOREVER);
+        }
+
+        self.game.as_deref().ok_or("no game was started")
+    }
+
+    fn book_move(&mut self, game: &mut dyn Game) -> Option<String> {
+        if !self.use_book {
+            return None;
+        }
+
+        let book = self.book.as_ref().filter(|book| book.size() == self.size)?;
+        let mv = book.choose(game.hash(), &mut self.rng)?.to_string();
+
+        // A hash collision or a corrupt book can suggest a move that does not fit the position
+        if !game.parser()(&mv).is_ok_and(|action| game.is_legal(action)) {
+            if self.debug {
+                println!("info string book move {mv} is illegal, searching instead");
+            }
+            return None;
+        }
+
+        if self.debug {
+            println!("info string book move {mv}");
+        }
+        Some(mv)
+    }
+
     async fn handle_command(&mut self, line: &str) -> bool {
         let mut cmd = line.split_ascii_whitespace();
         match cmd.next().unwrap() {
@@ -92,28 +244,85 @@ impl State {
             }
             "setoption" => {
                 assert_eq!(cmd.next().unwrap(), "name");
-                assert_eq!(cmd.next().unwrap(), "HalfKomi");
+                let name = cmd.next().unwrap();
                 assert_eq!(cmd.next().unwrap(), "value");
-
-                self.half_komi = cmd.next().unwrap().parse().unwrap();
-                assert!(self.half_komi >= MIN_KOMI);
-                assert!(self.half_komi <= MAX_KOMI);
+                let value = cmd.collect::<Vec<_>>().join(" ");
+                let value = value.as_str();
+
+                match name {
+                    "HalfKomi" => {
+                        self.half_komi = value.parse().unwrap();
+                        assert!(self.half_komi >= MIN_KOMI);
+                        assert!(self.half_komi <= MAX_KOMI);
+                    }
+                    "UsePvs" => self.use_pvs = value.parse().unwrap(),
+                    "UseNnue" if !cfg!(feature = "nnue") => {
+                        println!("info string UseNnue requires a build with the nnue feature");
+                    }
+                    "UseNnue" => self.use_nnue = value.parse().unwrap(),
+                    "NnueFile" => match read_file_option(value) {
+                        Ok(network) => self.network = network,
+                        Err(e) => println!("info string {e}"),
+                    },
+                    "OwnBook" => self.use_book = value.parse().unwrap(),
+                    "BookFile" => match read_file_option(value) {
+                        Ok(None) => self.book = None,
+                        Ok(Some(data)) => match Book::from_bytes(&data) {
+                            Some(book) => self.book = Some(book),
+                            None => println!("info string malformed book {value}"),
+                        },
+                        Err(e) => println!("info string {e}"),
+                    },
+                    _ => {
+                        let spin = SPINS
+                            .iter()
+                            .find(|spin| spin.name == name)
+                            .unwrap_or_else(|| panic!(r#"unsupported option "{name}""#));
+
+                        let value = value.parse().unwrap();
+                        assert!(value >= spin.min);
+                        assert!(value <= spin.max);
+                        self.spins.push((spin, value));
+                    }
+                }
             }
             "teinewgame" => {
                 let size = cmd.next().unwrap().parse().unwrap();
 
+                let mut params = Params {
+                    search: SEARCH_PARAMS,
+                    eval: eval_params(size).unwrap(),
+                };
+                params.search.use_pvs = self.use_pvs;
+                for &(spin, value) in &self.spins {
+                    (spin.set)(&mut params, value);
+                }
+
                 self.abort().await;
                 self.history.clear();
-                self.game = Some(
-                    new_game(
-                        size,
-                        Options {
-                            half_komi: self.half_komi,
-                            ..Options::default(size).unwrap()
-                        },
-                    )
-                    .unwrap(),
-                );
+                self.size = size;
+                let mut game = new_game(
+                    size,
+                    Options {
+                        half_komi: self.half_komi,
+                        params: params.search,
+                        eval: Some(params.eval),
+                        ..Options::default(size).unwrap()
+                    },
+                )
+                .unwrap();
+
+                if self.use_nnue {
+                    let loaded = match &self.network {
+                        Some(network) => game.set_network(network).map_err(|_| "malformed network"),
+                        None => Err("no network loaded"),
+                    };
+                    if let Err(e) = loaded {
+                        println!("info string {e}, using the handcrafted eval");
+                    }
+                }
+
+                self.game = Some(game);
             }
             "position" => {
                 assert_eq!(cmd.next().unwrap(), "startpos");
@@ -138,7 +347,7 @@ impl State {
                 let start = Instant::now();
 
                 self.abort().await;
-                let game = self.game.take().expect("can't start search");
+                let mut game = self.game.take().expect("can't start search");
 
                 let mut time = Pair::default();
                 let mut increment = Pair::default();
@@ -169,6 +378,14 @@ impl State {
                     };
                 }
 
+                if !delay_bestmove {
+                    if let Some(mv) = self.book_move(&mut *game) {
+                        println!("bestmove {mv}");
+                        self.game = Some(game);
+                        return false;
+                    }
+                }
+
                 let color = game.active_color();
 
                 // TODO: Improve?
@@ -206,6 +423,22 @@ impl State {
                     println!("info string target time = {time_target:?}");
                 }
             }
+            "eval" => match self.idle_game() {
+                Ok(game) => {
+                    for line in game.eval_trace().to_string().lines() {
+                        println!("info string {line}");
+                    }
+                }
+                Err(e) => println!("info string can't evaluate position: {e}"),
+            },
+            "d" => match self.idle_game() {
+                Ok(game) => {
+                    for line in game.board().to_string().lines() {
+                        println!("info string {line}");
+                    }
+                }
+                Err(e) => println!("info string can't display position: {e}"),
+            },
             "quit" => {
                 self.abort().await;
                 return true;
@@ -218,13 +451,49 @@ impl State {
     }
 }
 
-pub async fn run() {
+/// Runs the TEI loop. The `spin` options advertise the defaults of `size`, though any size can be
+/// played.
+/// Reads the file named by a string option, where the advertised default `<empty>` clears it.
+fn read_file_option(value: &str) -> Result<Option<Vec<u8>>, String> {
+    if value.is_empty() || value == "<empty>" {
+        return Ok(None);
+    }
+
+    fs::read(value)
+        .map(Some)
+        .map_err(|e| format!("can't read {value}: {e}"))
+}
+
+pub async fn run(size: usize) {
+    let defaults = Params {
+        search: SEARCH_PARAMS,
+        eval: eval_params(size).expect("unsupported size"),
+    };
     let mut lines = BufReader::new(stdin()).lines();
 
     assert_eq!(lines.next_line().await.unwrap().unwrap(), "tei");
     println!("id name cataklysm");
     println!("id author alion02");
     println!("option name HalfKomi type spin default 0 min {MIN_KOMI} max {MAX_KOMI}");
+    println!(
+        "option name UsePvs type check default {}",
+        defaults.search.use_pvs,
+    );
+    if cfg!(feature = "nnue") {
+        println!("option name UseNnue type check default false");
+        println!("option name NnueFile type string default <empty>");
+    }
+    println!("option name OwnBook type check default false");
+    println!("option name BookFile type string default <empty>");
+    for spin in SPINS {
+        println!(
+            "option name {} type spin default {} min {} max {}",
+            spin.name,
+            (spin.get)(&defaults),
+            spin.min,
+            spin.max,
+        );
+    }
     println!("teiok");
 
     let send = unbounded_channel::<Search>();
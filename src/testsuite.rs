@@ -0,0 +1,168 @@
+use std::time::{Duration, Instant};
+
+use common::game::*;
+
+use crate::{
+    new_game,
+    util::{think, Limit},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SuiteError {
+    /// The line with the given index is malformed.
+    Parse(usize),
+    UnsupportedSize(usize),
+    InvalidPosition(usize),
+    /// A move on the line with the given index could not be parsed.
+    InvalidMove(usize),
+}
+
+/// A position with the moves expected from a search, in the manner of an EPD test suite.
+///
+/// Each line of a suite holds a TPS followed by operations separated by semicolons, such as
+/// `x5/x5/1,1,1,1,x/x5/2,2,2,x2 1 5; bm e3; id "road in one"`. The operations are `bm` and
+/// `am` followed by moves which are the best or to be avoided, `depth` or `movetime` in
+/// milliseconds overriding the limit of the run, and `id` naming the position. Empty lines and
+/// those starting with `#` are ignored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestPosition {
+    pub id: Option<String>,
+    pub tps: String,
+    pub best: Vec<String>,
+    pub avoid: Vec<String>,
+    pub limit: Option<Limit>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TestOutcome {
+    pub passed: bool,
+    pub answer: String,
+    pub eval: Eval,
+    pub nodes: u64,
+    pub time: Duration,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SuiteSummary {
+    pub passed: usize,
+    pub failed: usize,
+    pub nodes: u64,
+    pub time: Duration,
+}
+
+impl TestPosition {
+    /// Searches the position and checks the answer against the expected moves.
+    ///
+    /// Moves are compared after being read by the parser of the game, so that any accepted
+    /// spelling of a move matches.
+    pub fn run(&self, line: usize, default: Limit) -> Result<TestOutcome, SuiteError> {
+        let size = size_of_tps(&self.tps);
+        let opt = Options::default(size).ok_or(SuiteError::UnsupportedSize(line))?;
+        let mut game = new_game(size, opt).map_err(|_| SuiteError::UnsupportedSize(line))?;
+        game.set_position(&self.tps)
+            .map_err(|_| SuiteError::InvalidPosition(line))?;
+
+        let parse = game.parser();
+        let normalize = |moves: &[String]| {
+            moves
+                .iter()
+                .map(|mv| parse(mv).map(|action| action.to_string()))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| SuiteError::InvalidMove(line))
+        };
+        let best = normalize(&self.best)?;
+        let avoid = normalize(&self.avoid)?;
+
+        // There is no move to search for once the game is over
+        if game.result().is_some() {
+            return Err(SuiteError::InvalidPosition(line));
+        }
+
+        let start = Instant::now();
+        let (eval, _, answer) = think(&mut *game, self.limit.unwrap_or(default));
+        let answer = answer.to_string();
+
+        Ok(TestOutcome {
+            passed: (best.is_empty() || best.contains(&answer)) && !avoid.contains(&answer),
+            answer,
+            eval,
+            nodes: game.nodes(),
+            time: start.elapsed(),
+        })
+    }
+}
+
+/// Parses a test suite into positions paired with the index of their line.
+pub fn parse_suite(text: &str) -> Result<Vec<(usize, TestPosition)>, SuiteError> {
+    let mut positions = Vec::new();
+
+    for (line, text) in text.lines().enumerate() {
+        let text = text.trim();
+        if text.is_empty() || text.starts_with('#') {
+            continue;
+        }
+
+        let mut ops = text.split(';').map(str::trim);
+        let mut position = TestPosition {
+            id: None,
+            tps: ops.next().unwrap().to_string(),
+            best: Vec::new(),
+            avoid: Vec::new(),
+            limit: None,
+        };
+
+        for op in ops.filter(|op| !op.is_empty()) {
+            let (name, args) = op.split_once(' ').ok_or(SuiteError::Parse(line))?;
+            let args = args.trim();
+            match name {
+                "bm" => position
+                    .best
+                    .extend(args.split_whitespace().map(String::from)),
+                "am" => position
+                    .avoid
+                    .extend(args.split_whitespace().map(String::from)),
+                "depth" => {
+                    let depth = args.parse().map_err(|_| SuiteError::Parse(line))?;
+                    position.limit = Some(Limit::Depth(depth));
+                }
+                "movetime" => {
+                    let ms = args.parse().map_err(|_| SuiteError::Parse(line))?;
+                    position.limit = Some(Limit::Movetime(Duration::from_millis(ms)));
+                }
+                "id" => position.id = Some(args.trim_matches('"').to_string()),
+                _ => return Err(SuiteError::Parse(line)),
+            }
+        }
+
+        if position.best.is_empty() && position.avoid.is_empty() {
+            return Err(SuiteError::Parse(line));
+        }
+        positions.push((line, position));
+    }
+
+    Ok(positions)
+}
+
+/// Runs every position of a suite, calling `f` with each outcome.
+pub fn run_suite(
+    positions: &[(usize, TestPosition)],
+    default: Limit,
+    mut f: impl FnMut(&TestPosition, &TestOutcome),
+) -> Result<SuiteSummary, SuiteError> {
+    let mut summary = SuiteSummary::default();
+
+    for (line, position) in positions {
+        let outcome = position.run(*line, default)?;
+        f(position, &outcome);
+
+        if outcome.passed {
+            summary.passed += 1;
+        } else {
+            summary.failed += 1;
+        }
+        summary.nodes += outcome.nodes;
+        summary.time += outcome.time;
+    }
+
+    Ok(summary)
+}
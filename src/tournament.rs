@@ -0,0 +1,495 @@
+use std::{
+    env::Args,
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    path::Path,
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{channel, Receiver, RecvTimeoutError},
+    thread::spawn,
+    time::{Duration, Instant},
+};
+
+use cataklysm::{
+    new_game,
+    ptn::PtnGame,
+    util::{elo, random_opening, Rng},
+};
+use common::{game::*, pair::Pair};
+
+const DEFAULT_TIME: Duration = Duration::from_secs(10);
+const DEFAULT_INCREMENT: Duration = Duration::from_millis(100);
+const DEFAULT_ROUNDS: u32 = 1;
+const DEFAULT_SEED: u64 = 1;
+
+const MAX_PLIES: usize = 400;
+const RANDOM_PLIES: usize = 4;
+
+/// Time an engine has to answer outside of a search.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Allowance for process and pipe latency before a move counts as over time.
+const TIME_MARGIN: Duration = Duration::from_millis(50);
+
+/// A running engine process, with its output lines read by a separate thread.
+struct Process {
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+}
+
+impl Process {
+    fn spawn(command: &[String]) -> io::Result<Self> {
+        let mut child = Command::new(&command[0])
+            .args(&command[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+
+        let (tx, lines) = channel();
+        spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self {
+            child,
+            stdin,
+            lines,
+        })
+    }
+
+    fn send(&mut self, line: &str) -> Option<()> {
+        writeln!(self.stdin, "{line}").ok()?;
+        self.stdin.flush().ok()
+    }
+
+    /// Waits for a line satisfying `f`, returning `None` if the engine exits or the deadline
+    /// passes first.
+    fn wait_for(&self, deadline: Instant, mut f: impl FnMut(&str) -> bool) -> Option<String> {
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match self.lines.recv_timeout(timeout) {
+                Ok(line) if f(&line) => return Some(line),
+                Ok(_) => {}
+                Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => return None,
+            }
+        }
+    }
+}
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Failure {
+    Crash,
+    Timeout,
+}
+
+struct Engine {
+    name: String,
+    command: Vec<String>,
+    options: Vec<(String, String)>,
+    /// Started on demand, and dropped after a failure so that the next game restarts it.
+    process: Option<Process>,
+}
+
+impl Engine {
+    fn start(&mut self, half_komi: i32) -> Result<&mut Process, Failure> {
+        if self.process.is_none() {
+            let mut process = Process::spawn(&self.command).map_err(|_| Failure::Crash)?;
+            process.send("tei").ok_or(Failure::Crash)?;
+
+            let deadline = Instant::now() + RESPONSE_TIMEOUT;
+            process
+                .wait_for(deadline, |line| line == "teiok")
+                .ok_or(Failure::Timeout)?;
+
+            if half_komi != 0 {
+                process
+                    .send(&format!("setoption name HalfKomi value {half_komi}"))
+                    .ok_or(Failure::Crash)?;
+            }
+            for (name, value) in &self.options {
+                process
+                    .send(&format!("setoption name {name} value {value}"))
+                    .ok_or(Failure::Crash)?;
+            }
+
+            self.process = Some(process);
+        }
+
+        Ok(self.process.as_mut().unwrap())
+    }
+
+    fn new_game(&mut self, size: usize, half_komi: i32) -> Result<(), Failure> {
+        let result = self.start(half_komi).and_then(|process| {
+            process
+                .send(&format!("teinewgame {size}"))
+                .ok_or(Failure::Crash)?;
+            process.send("isready").ok_or(Failure::Crash)?;
+            process
+                .wait_for(Instant::now() + RESPONSE_TIMEOUT, |line| line == "readyok")
+                .ok_or(Failure::Timeout)?;
+            Ok(())
+        });
+
+        if result.is_err() {
+            self.process = None;
+        }
+        result
+    }
+
+    /// Asks for a move in the position reached by the moves, returning it with the time taken.
+    fn go(
+        &mut self,
+        moves: &[String],
+        clocks: Pair<Duration>,
+        increment: Duration,
+    ) -> Result<(String, Duration), Failure> {
+        let color = moves.len() % 2 == 1;
+        let process = self.process.as_mut().ok_or(Failure::Crash)?;
+
+        let result = (|| {
+            process
+                .send(&format!("position startpos moves {}", moves.join(" ")))
+                .ok_or(Failure::Crash)?;
+
+            let start = Instant::now();
+            process
+                .send(&format!(
+                    "go wtime {} btime {} winc {inc} binc {inc}",
+                    clocks.white.as_millis(),
+                    clocks.black.as_millis(),
+                    inc = increment.as_millis(),
+                ))
+                .ok_or(Failure::Crash)?;
+
+            let deadline = start + clocks[color] + TIME_MARGIN;
+            let line = process
+                .wait_for(deadline, |line| line.starts_with("bestmove "))
+                .ok_or(if Instant::now() >= deadline {
+                    Failure::Timeout
+                } else {
+                    Failure::Crash
+                })?;
+
+            let mv = line["bestmove ".len()..].trim().to_string();
+            Ok((mv, start.elapsed()))
+        })();
+
+        if result.is_err() {
+            self.process = None;
+        }
+        result
+    }
+}
+
+/// Score of an engine over the tournament.
+#[derive(Clone, Copy, Default)]
+struct Standing {
+    wins: u32,
+    draws: u32,
+    losses: u32,
+}
+
+impl Standing {
+    fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    fn points(&self) -> f64 {
+        self.wins as f64 + self.draws as f64 / 2.
+    }
+}
+
+/// Plays TEI engines against each other, checking their moves and enforcing their clocks.
+pub fn run(mut args: Args) {
+    let size: usize = args.next().expect("missing size").parse().unwrap();
+
+    let mut engines = Vec::<Engine>::new();
+    let mut time = DEFAULT_TIME;
+    let mut increment = DEFAULT_INCREMENT;
+    let mut rounds = DEFAULT_ROUNDS;
+    let mut seed = DEFAULT_SEED;
+    let mut half_komi = 0;
+    let mut gauntlet = false;
+    let mut openings = None;
+    let mut ptn_path = None;
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().expect("missing option value");
+        match arg.as_str() {
+            "--engine" => {
+                let command = value()
+                    .split_whitespace()
+                    .map(String::from)
+                    .collect::<Vec<_>>();
+                assert!(!command.is_empty(), "empty engine command");
+                engines.push(Engine {
+                    name: String::new(),
+                    command,
+                    options: Vec::new(),
+                    process: None,
+                });
+            }
+            "--name" => engines.last_mut().expect("no engine to name").name = value(),
+            "--option" => {
+                let option = value();
+                let (name, value) = option.split_once('=').expect("malformed option");
+                let engine = engines.last_mut().expect("no engine to set the option of");
+                engine.options.push((name.to_string(), value.to_string()));
+            }
+            "--tc" => {
+                let tc = value();
+                let (base, inc) = tc.split_once('+').expect("malformed time control");
+                time = Duration::from_secs_f64(base.parse().unwrap());
+                increment = Duration::from_secs_f64(inc.parse().unwrap());
+            }
+            "--rounds" => rounds = value().parse().unwrap(),
+            "--seed" => seed = value().parse().unwrap(),
+            "--komi" => half_komi = value().parse().unwrap(),
+            "--gauntlet" => gauntlet = true,
+            "--openings" => openings = Some(value()),
+            "--ptn" => ptn_path = Some(value()),
+            _ => panic!(r#"unsupported option "{arg}""#),
+        }
+    }
+
+    assert!(engines.len() >= 2, "at least two engines are required");
+    for (i, engine) in engines.iter_mut().enumerate() {
+        if engine.name.is_empty() {
+            let file = Path::new(&engine.command[0]).file_name().unwrap();
+            engine.name = format!("{}#{}", file.to_string_lossy(), i + 1);
+        }
+    }
+
+    // Openings are space-separated moves, one per line
+    let openings = openings.map(|path| {
+        fs::read_to_string(path)
+            .expect("could not read openings")
+            .lines()
+            .map(|line| {
+                line.split_whitespace()
+                    .map(String::from)
+                    .collect::<Vec<_>>()
+            })
+            .filter(|moves| !moves.is_empty())
+            .collect::<Vec<_>>()
+    });
+
+    let pairings = (0..engines.len())
+        .flat_map(|a| (a + 1..engines.len()).map(move |b| (a, b)))
+        .filter(|&(a, _)| !gauntlet || a == 0)
+        .collect::<Vec<_>>();
+
+    let mut rng = Rng(seed);
+    let mut standings = vec![Standing::default(); engines.len()];
+    let mut games = Vec::new();
+
+    for round in 1..=rounds {
+        for &(a, b) in &pairings {
+            let opening = match &openings {
+                Some(openings) => openings[rng.below(openings.len())].clone(),
+                None => random_opening(size, RANDOM_PLIES, &mut rng),
+            };
+
+            for (white, black) in [(a, b), (b, a)] {
+                let players = pair_mut(&mut engines, white, black);
+                let (score, mut ptn, reason) =
+                    play_game(size, half_komi, &opening, players, time, increment);
+
+                ptn.set_tag("Round", round.to_string());
+                println!(
+                    "game {}: {} vs {}, {} ({reason})",
+                    games.len() + 1,
+                    engines[white].name,
+                    engines[black].name,
+                    ptn.result().map_or("*".into(), |result| result.to_string()),
+                );
+
+                for (i, score) in [(white, score), (black, 1. - score)] {
+                    match score {
+                        s if s > 0.5 => standings[i].wins += 1,
+                        s if s < 0.5 => standings[i].losses += 1,
+                        _ => standings[i].draws += 1,
+                    }
+                }
+
+                games.push(ptn.to_string());
+                if let Some(path) = &ptn_path {
+                    fs::write(path, games.join("\n")).unwrap();
+                }
+            }
+        }
+    }
+
+    print_standings(&engines, &standings);
+}
+
+fn pair_mut(engines: &mut [Engine], a: usize, b: usize) -> [&mut Engine; 2] {
+    if a < b {
+        let (left, right) = engines.split_at_mut(b);
+        [&mut left[a], &mut right[0]]
+    } else {
+        let (left, right) = engines.split_at_mut(a);
+        [&mut right[0], &mut left[b]]
+    }
+}
+
+/// Plays a game from the opening, returning the score of white with the game and the reason it
+/// ended.
+fn play_game(
+    size: usize,
+    half_komi: i32,
+    opening: &[String],
+    mut players: [&mut Engine; 2],
+    time: Duration,
+    increment: Duration,
+) -> (f64, PtnGame, String) {
+    let mut ptn = PtnGame::new(size, half_komi);
+    ptn.set_tag("Player1", players[0].name.as_str());
+    ptn.set_tag("Player2", players[1].name.as_str());
+    ptn.set_tag(
+        "Clock",
+        format!(
+            "{}:{:02} +{}",
+            time.as_secs() / 60,
+            time.as_secs() % 60,
+            increment.as_secs_f64(),
+        ),
+    );
+
+    let mut game = new_game(
+        size,
+        Options {
+            half_komi,
+            ..Options::default(size).unwrap()
+        },
+    )
+    .unwrap();
+
+    let mut moves = Vec::new();
+    for mv in opening {
+        let action = game.parser()(mv).expect("invalid opening move");
+        moves.push(action.to_string());
+        ptn.push(&action, Vec::new()).unwrap();
+        game.play(action).expect("illegal opening move");
+    }
+
+    let started = players
+        .iter_mut()
+        .map(|player| player.new_game(size, half_komi))
+        .collect::<Vec<_>>();
+    if let Some(color) = started.iter().position(Result::is_err) {
+        let (score, reason) = forfeit(&mut ptn, color == 1, "engine failed to start".into());
+        return (score, ptn, reason);
+    }
+
+    let mut clocks = Pair::both(time);
+
+    let (score, reason) = loop {
+        if let Some(result) = game.result() {
+            ptn.set_result(result);
+            break (result.white_score(), "game over".into());
+        }
+
+        if moves.len() >= MAX_PLIES {
+            ptn.set_result(GameResult::Draw);
+            break (0.5, "move limit".into());
+        }
+
+        let color = game.active_color();
+        let player = &mut players[color as usize];
+
+        let (mv, elapsed) = match player.go(&moves, clocks, increment) {
+            Ok(answer) => answer,
+            Err(Failure::Timeout) => break forfeit(&mut ptn, color, "time forfeit".into()),
+            Err(Failure::Crash) => break forfeit(&mut ptn, color, "engine crashed".into()),
+        };
+
+        let Some(left) = clocks[color].checked_sub(elapsed.saturating_sub(TIME_MARGIN)) else {
+            break forfeit(&mut ptn, color, "time forfeit".into());
+        };
+        clocks[color] = left + increment;
+
+        let Ok(action) = game.parser()(&mv) else {
+            break forfeit(&mut ptn, color, format!("unreadable move {mv}"));
+        };
+        let normalized = action.to_string();
+        if game.play(action).is_err() {
+            break forfeit(&mut ptn, color, format!("illegal move {mv}"));
+        }
+
+        ptn.push(&normalized, Vec::new()).unwrap();
+        moves.push(normalized);
+    };
+
+    (score, ptn, reason)
+}
+
+/// Records a loss of `color`, the side at fault when an engine fails, and returns the score of
+/// white with the reason.
+fn forfeit(ptn: &mut PtnGame, color: bool, reason: String) -> (f64, String) {
+    ptn.set_other_win(!color);
+    (if color { 1. } else { 0. }, reason)
+}
+
+fn print_standings(engines: &[Engine], standings: &[Standing]) {
+    let mut order = (0..engines.len()).collect::<Vec<_>>();
+    order.sort_by(|&a, &b| standings[b].points().total_cmp(&standings[a].points()));
+
+    let width = engines.iter().map(|e| e.name.len()).max().unwrap().max(6);
+    println!(
+        "{:>4}  {:<width$}  {:>6}  {:>6}  {:>14}  {:>7}  {:>7}",
+        "rank", "engine", "games", "points", "+/=/-", "score", "elo",
+    );
+
+    for (rank, &i) in order.iter().enumerate() {
+        let standing = standings[i];
+        let score = standing.points() / standing.games().max(1) as f64;
+        println!(
+            "{:>4}  {:<width$}  {:>6}  {:>6.1}  {:>14}  {:>6.1}%  {:>+7.1}",
+            rank + 1,
+            engines[i].name,
+            standing.games(),
+            standing.points(),
+            format!("{}/{}/{}", standing.wins, standing.draws, standing.losses),
+            score * 100.,
+            elo(score),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::pair::{BLACK, WHITE};
+
+    use super::*;
+
+    #[test]
+    fn forfeit_records_a_loss() {
+        for (color, result, score) in [(WHITE, "0-1", 0.), (BLACK, "1-0", 1.)] {
+            let mut ptn = PtnGame::new(5, 0);
+            ptn.push("a1", Vec::new()).unwrap();
+
+            let (white_score, _) = forfeit(&mut ptn, color, "time forfeit".into());
+            assert_eq!(white_score, score);
+            assert!(ptn.to_string().contains(&format!("[Result \"{result}\"]")));
+        }
+    }
+}
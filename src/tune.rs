@@ -0,0 +1,250 @@
+use std::{env::Args, fs, io::BufRead, time::Instant};
+
+use cataklysm::{eval_params, new_game};
+use common::{
+    game::*,
+    pair::WHITE,
+    params::{EvalFeatures, EvalWeights, SearchParams, PHASE_MAX, SEARCH_PARAMS},
+};
+
+use crate::datagen;
+
+const STEPS: [i32; 4] = [8, 4, 2, 1];
+
+/// A tunable evaluation weight and its matching feature.
+struct Term {
+    feature: fn(&EvalFeatures) -> i32,
+    weight: fn(&mut EvalWeights) -> &mut i32,
+}
+
+macro_rules! term {
+    ($field:ident) => {
+        Term {
+            feature: |f| f.$field,
+            weight: |p| &mut p.$field,
+        }
+    };
+}
+
+// `max_dist_offset` changes the features themselves, so it cannot be tuned this way
+const TERMS: &[Term] = &[
+    term!(flat_count),
+    term!(stones_left),
+    term!(caps_left),
+    term!(total_dist),
+    term!(smallest_dist),
+    term!(side_to_move),
+    term!(reserves),
+    term!(captives),
+    term!(cap_stack),
+];
+
+struct Sample {
+    /// Each feature scaled by the opening share of the phase, followed by the same for the endgame.
+    features: Vec<f64>,
+    /// Result from the point of view of the side to move: 0 for a loss, 0.5 for a draw, 1 for a win.
+    result: f64,
+}
+
+pub fn run(mut args: Args) {
+    let path = args.next().expect("missing dataset path");
+
+    let time = Instant::now();
+    let (size, samples) = load(&path);
+    println!(
+        "loaded {} positions in {:.2}s",
+        samples.len(),
+        time.elapsed().as_secs_f64(),
+    );
+
+    if samples.is_empty() {
+        return;
+    }
+
+    let mut params = eval_params(size).expect("unsupported size");
+    let mut weights = [&mut params.opening, &mut params.endgame]
+        .into_iter()
+        .flat_map(|weights| TERMS.iter().map(|term| *(term.weight)(weights)))
+        .collect::<Vec<_>>();
+
+    let k = fit_scale(&samples, &weights);
+    println!("scale k = {k:.6}");
+
+    let mut best = error(&samples, &weights, k);
+    println!("initial error {best:.6}");
+
+    for step in STEPS {
+        let mut improved = true;
+        while improved {
+            improved = false;
+
+            for i in 0..weights.len() {
+                for delta in [step, -step] {
+                    weights[i] += delta;
+
+                    let e = error(&samples, &weights, k);
+                    if e < best {
+                        best = e;
+                        improved = true;
+                        break;
+                    }
+
+                    weights[i] -= delta;
+                }
+            }
+
+            println!("step {step}: error {best:.6}, weights {weights:?}");
+        }
+    }
+
+    let (opening, endgame) = weights.split_at(TERMS.len());
+    for (params, weights) in [
+        (&mut params.opening, opening),
+        (&mut params.endgame, endgame),
+    ] {
+        for (term, &weight) in TERMS.iter().zip(weights) {
+            *(term.weight)(params) = weight;
+        }
+    }
+
+    println!("pub static SIZE_EVAL_PARAMS: EvalParams = {params:#?};");
+}
+
+/// Returns the board size shared by all positions and the samples, from a dataset written by
+/// `datagen` or from text lines.
+fn load(path: &str) -> (usize, Vec<Sample>) {
+    let data = fs::read(path).expect("could not open dataset");
+
+    if data.starts_with(datagen::MAGIC) {
+        let (size, records) = datagen::read(&data).expect("malformed dataset");
+        let mut game = tuning_game(size).expect("unsupported size");
+
+        let samples = records
+            .iter()
+            .map(|record| {
+                game.decode_position(&record.position)
+                    .expect("malformed position");
+                sample_of(&*game, record.result.white_score())
+            })
+            .collect();
+
+        return (size, samples);
+    }
+
+    let mut size = None;
+    let mut samples = Vec::new();
+    let mut skipped = 0;
+    for line in data.as_slice().lines() {
+        let line = line.unwrap();
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match sample(&line) {
+            Some((sample_size, sample)) if *size.get_or_insert(sample_size) == sample_size => {
+                samples.push(sample)
+            }
+            _ => skipped += 1,
+        }
+    }
+
+    if skipped != 0 {
+        println!("skipped {skipped} malformed lines or positions of another size");
+    }
+
+    (size.unwrap_or_default(), samples)
+}
+
+/// Parses a `<tps>;...;<result>` line.
+fn sample(line: &str) -> Option<(usize, Sample)> {
+    let mut fields = line.split(';').map(str::trim);
+    let tps = fields.next()?;
+    let result = fields.next_back()?;
+
+    let white_result = match result {
+        "R-0" | "F-0" | "1-0" => 1.,
+        "0-R" | "0-F" | "0-1" => 0.,
+        "1/2-1/2" => 0.5,
+        _ => return None,
+    };
+
+    let size = size_of_tps(tps);
+    let mut game = tuning_game(size)?;
+    game.set_position(tps).ok()?;
+
+    Some((size, sample_of(&*game, white_result)))
+}
+
+fn tuning_game(size: usize) -> Option<Box<dyn Game>> {
+    new_game(
+        size,
+        Options {
+            params: SearchParams {
+                tt_size: 1,
+                ..SEARCH_PARAMS
+            },
+            ..Options::default(size)?
+        },
+    )
+    .ok()
+}
+
+fn sample_of(game: &dyn Game, white_result: f64) -> Sample {
+    let features = game.eval_features();
+    let opening = features.phase as f64 / PHASE_MAX as f64;
+
+    Sample {
+        features: [opening, 1. - opening]
+            .into_iter()
+            .flat_map(|share| {
+                TERMS
+                    .iter()
+                    .map(move |term| (term.feature)(&features) as f64 * share)
+            })
+            .collect(),
+        result: if game.active_color() == WHITE {
+            white_result
+        } else {
+            1. - white_result
+        },
+    }
+}
+
+fn sigmoid(eval: f64, k: f64) -> f64 {
+    1. / (1. + (-k * eval).exp())
+}
+
+fn error(samples: &[Sample], weights: &[i32], k: f64) -> f64 {
+    let total = samples
+        .iter()
+        .map(|sample| {
+            let eval = sample
+                .features
+                .iter()
+                .zip(weights)
+                .map(|(&f, &w)| f * w as f64)
+                .sum::<f64>();
+
+            (sample.result - sigmoid(eval, k)).powi(2)
+        })
+        .sum::<f64>();
+
+    total / samples.len() as f64
+}
+
+/// Finds the sigmoid scale that best fits the initial weights, by ternary search.
+fn fit_scale(samples: &[Sample], weights: &[i32]) -> f64 {
+    let (mut lo, mut hi) = (0., 1.);
+    for _ in 0..100 {
+        let a = lo + (hi - lo) / 3.;
+        let b = hi - (hi - lo) / 3.;
+
+        if error(samples, weights, a) < error(samples, weights, b) {
+            hi = b;
+        } else {
+            lo = a;
+        }
+    }
+
+    (lo + hi) / 2.
+}
@@ -0,0 +1,131 @@
+use std::{
+    sync::mpsc::{channel, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use common::game::*;
+
+const MAX_SEARCH_DEPTH: u32 = 30;
+
+/// SplitMix64, so that runs are reproducible from the seed alone.
+pub struct Rng(pub u64);
+
+impl Rng {
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+/// Returns placements on distinct random squares, which are always legal.
+pub fn random_opening(size: usize, plies: usize, rng: &mut Rng) -> Vec<String> {
+    let mut squares = (0..size * size).collect::<Vec<_>>();
+
+    (0..plies)
+        .map(|_| {
+            let sq = squares.swap_remove(rng.below(squares.len()));
+            format!("{}{}", (b'a' + (sq % size) as u8) as char, sq / size + 1)
+        })
+        .collect()
+}
+
+/// Converts an expected score to an Elo difference, clamping decisive scores to a finite value.
+pub fn elo(score: f64) -> f64 {
+    -400. * (1. / score.clamp(1e-6, 1. - 1e-6) - 1.).log10()
+}
+
+/// Limit of a search by [`think`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Nodes(u64),
+    Depth(u32),
+    Movetime(Duration),
+}
+
+/// Searches with iterative deepening until the limit is reached or the eval is decisive, and
+/// returns the eval, the last completed depth and the best move.
+///
+/// The first depth always completes, so that there is a move to play. Later depths are aborted
+/// once the nodes or the time run out, and do not start if the previous depths suggest that they
+/// cannot finish in time.
+pub fn think(game: &mut dyn Game, limit: Limit) -> (Eval, u32, Box<dyn Move>) {
+    let start = Instant::now();
+    game.clear_nodes();
+    game.clear_abort_flag();
+
+    let (mut eval, mut action) = (0..128)
+        .find_map(|_| game.search(1))
+        .expect("failed to get a tt entry 128 times");
+
+    let max_depth = match limit {
+        Limit::Depth(depth) => depth,
+        Limit::Nodes(nodes) => {
+            game.set_node_limit(nodes);
+            MAX_SEARCH_DEPTH
+        }
+        Limit::Movetime(_) => MAX_SEARCH_DEPTH,
+    };
+
+    // Sets the abort flag when the time runs out, unless dropping the sender stops it first
+    let timer = if let Limit::Movetime(time) = limit {
+        let flag = game.abort_flag();
+        let (stop, stopped) = channel::<()>();
+        let left = time.saturating_sub(start.elapsed());
+        let handle = thread::spawn(move || {
+            if stopped.recv_timeout(left) == Err(RecvTimeoutError::Timeout) {
+                flag.set();
+            }
+        });
+        Some((stop, handle))
+    } else {
+        None
+    };
+
+    let mut depth = 1;
+    let mut last = Duration::ZERO;
+    while depth < max_depth && !eval.is_decisive() {
+        if let Limit::Movetime(time) = limit {
+            // Stop if the next depth is expected to exceed the time, as the previous one took a
+            // fraction of the time of this one
+            let elapsed = start.elapsed();
+            let ratio = elapsed.as_secs_f64() / last.as_secs_f64().max(1e-3);
+            if elapsed.mul_f64(ratio.max(1.)) > time {
+                break;
+            }
+            last = elapsed;
+        }
+
+        // An aborted search leaves the result of the previous depth in the table
+        if let Some(result) = game.search(depth + 1) {
+            (eval, action) = result;
+        }
+        if game.clear_abort_flag() {
+            break;
+        }
+        depth += 1;
+    }
+
+    if let Some((stop, handle)) = timer {
+        drop(stop);
+        handle.join().unwrap();
+    }
+    game.set_node_limit(u64::MAX);
+    game.clear_abort_flag();
+
+    (eval, depth, action)
+}
+
+/// Share of the remaining time to spend on a move, assuming that 2/3 of the moves left are
+/// placements, as the TEI time management does.
+pub fn time_budget(game: &dyn Game, time: Duration, increment: Duration) -> Duration {
+    let moves_left = game.stones_left()[game.active_color()] * 3 / 2;
+    (time + increment * moves_left) / (moves_left + 1)
+}